@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS media
+    DROP COLUMN IF EXISTS telegram_file_id;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+
+ALTER TABLE IF EXISTS media
+    ADD COLUMN IF NOT EXISTS telegram_file_id character varying(255);
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+-- Media moved out of Postgres must be moved back before reverting.
+
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ALTER COLUMN data SET NOT NULL,
+    DROP COLUMN IF EXISTS storage_key,
+    DROP COLUMN IF EXISTS storage;
+
+DROP TYPE IF EXISTS media_storage;
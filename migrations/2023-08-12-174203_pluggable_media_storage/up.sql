@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+CREATE TYPE media_storage AS ENUM (
+    'postgres',
+    'filesystem',
+    's3'
+);
+
+ALTER TABLE IF EXISTS media
+    ADD COLUMN IF NOT EXISTS storage media_storage NOT NULL DEFAULT 'postgres',
+    ADD COLUMN IF NOT EXISTS storage_key character varying(1024),
+    ALTER COLUMN data DROP NOT NULL,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
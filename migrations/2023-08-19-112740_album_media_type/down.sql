@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- Postgres does not support removing enum values, 'album' stays in 'media_type'.
+
+SELECT 1;
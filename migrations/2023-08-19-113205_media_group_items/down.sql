@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM tag_to_media WHERE media_id IN (SELECT id FROM media WHERE type = 'album');
+DELETE FROM media_to_feature WHERE media_id IN (SELECT id FROM media WHERE type = 'album');
+DELETE FROM media_to_cron_job WHERE media_id IN (SELECT id FROM media WHERE type = 'album');
+DROP TABLE IF EXISTS media_group_items;
+DELETE FROM media WHERE type = 'album';
+
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
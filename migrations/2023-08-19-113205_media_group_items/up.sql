@@ -0,0 +1,24 @@
+-- Your SQL goes here
+
+CREATE TABLE IF NOT EXISTS media_group_items (
+    id serial PRIMARY KEY,
+    group_media_id INT NOT NULL,
+    media_id INT NOT NULL,
+    position INT NOT NULL DEFAULT 0,
+    caption character varying(1024),
+    CONSTRAINT fk_media_group_items_group
+        FOREIGN KEY(group_media_id)
+        REFERENCES media(id),
+    CONSTRAINT fk_media_group_items_media
+        FOREIGN KEY(media_id)
+        REFERENCES media(id)
+);
+
+-- albums have no blob of their own, their items are media rows linked above
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        type = 'album'
+        OR (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS cron_jobs
+    DROP COLUMN IF EXISTS parse_mode;
+
+ALTER TABLE IF EXISTS media
+    DROP COLUMN IF EXISTS parse_mode,
+    DROP COLUMN IF EXISTS caption;
+
+DROP TYPE IF EXISTS text_parse_mode;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+
+CREATE TYPE text_parse_mode AS ENUM (
+    'none',
+    'markdown_v2',
+    'html'
+);
+
+ALTER TABLE IF EXISTS media
+    ADD COLUMN IF NOT EXISTS caption character varying(1024),
+    ADD COLUMN IF NOT EXISTS parse_mode text_parse_mode NOT NULL DEFAULT 'none';
+
+ALTER TABLE IF EXISTS cron_jobs
+    ADD COLUMN IF NOT EXISTS parse_mode text_parse_mode NOT NULL DEFAULT 'none';
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TYPE media_type ADD VALUE 'script';
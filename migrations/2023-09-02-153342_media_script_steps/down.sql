@@ -0,0 +1,16 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM tag_to_media WHERE media_id IN (SELECT id FROM media WHERE type = 'script');
+DELETE FROM media_to_feature WHERE media_id IN (SELECT id FROM media WHERE type = 'script');
+DELETE FROM media_to_cron_job WHERE media_id IN (SELECT id FROM media WHERE type = 'script');
+DROP TABLE IF EXISTS media_script_steps;
+DROP TYPE IF EXISTS script_step_kind;
+DELETE FROM media WHERE type = 'script';
+
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        type = 'album'
+        OR (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+
+CREATE TYPE script_step_kind AS ENUM (
+    'chat_action',
+    'delay',
+    'media'
+);
+
+CREATE TABLE IF NOT EXISTS media_script_steps (
+    id serial PRIMARY KEY,
+    script_media_id INT NOT NULL,
+    position INT NOT NULL DEFAULT 0,
+    kind script_step_kind NOT NULL,
+    chat_action character varying(32),
+    min_delay_ms INT,
+    max_delay_ms INT,
+    media_id INT,
+    CONSTRAINT fk_media_script_steps_script
+        FOREIGN KEY(script_media_id)
+        REFERENCES media(id),
+    CONSTRAINT fk_media_script_steps_media
+        FOREIGN KEY(media_id)
+        REFERENCES media(id),
+    CONSTRAINT media_script_steps_kind_check CHECK (
+        (kind = 'chat_action' AND chat_action IS NOT NULL)
+        OR (kind = 'delay' AND min_delay_ms IS NOT NULL AND max_delay_ms >= min_delay_ms)
+        OR (kind = 'media' AND media_id IS NOT NULL)
+    )
+);
+
+-- scripts have no blob of their own, their media are linked above
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        type IN ('album', 'script')
+        OR (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+ALTER TYPE media_type ADD VALUE 'audio';
+ALTER TYPE media_type ADD VALUE 'location';
+ALTER TYPE media_type ADD VALUE 'venue';
+ALTER TYPE media_type ADD VALUE 'dice';
+ALTER TYPE media_type ADD VALUE 'poll';
+ALTER TYPE media_type ADD VALUE 'contact';
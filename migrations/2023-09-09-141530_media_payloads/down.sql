@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM tag_to_media WHERE media_id IN (SELECT id FROM media WHERE type IN ('location', 'venue', 'dice', 'poll', 'contact'));
+DELETE FROM media_to_feature WHERE media_id IN (SELECT id FROM media WHERE type IN ('location', 'venue', 'dice', 'poll', 'contact'));
+DELETE FROM media_to_cron_job WHERE media_id IN (SELECT id FROM media WHERE type IN ('location', 'venue', 'dice', 'poll', 'contact'));
+DELETE FROM media_script_steps WHERE media_id IN (SELECT id FROM media WHERE type IN ('location', 'venue', 'dice', 'poll', 'contact'));
+DELETE FROM media WHERE type IN ('location', 'venue', 'dice', 'poll', 'contact');
+
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        type IN ('album', 'script')
+        OR (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
+
+ALTER TABLE IF EXISTS media DROP COLUMN IF EXISTS payload;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+ALTER TABLE IF EXISTS media ADD COLUMN IF NOT EXISTS payload jsonb;
+
+-- locations, venues, dice, polls and contacts are described by their payload only
+ALTER TABLE IF EXISTS media
+    DROP CONSTRAINT IF EXISTS media_storage_location_check,
+    ADD CONSTRAINT media_storage_location_check CHECK (
+        type IN ('album', 'script')
+        OR (type IN ('location', 'venue', 'dice', 'poll', 'contact') AND payload IS NOT NULL)
+        OR (storage = 'postgres' AND data IS NOT NULL)
+        OR (storage <> 'postgres' AND storage_key IS NOT NULL)
+    );
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS pending_deletions;
+
+-- chats is kept, it may have been created by hand before this migration
+ALTER TABLE IF EXISTS chats DROP COLUMN IF EXISTS reply_ttl_secs;
+ALTER TABLE IF EXISTS tags DROP COLUMN IF EXISTS reply_ttl_secs;
+ALTER TABLE IF EXISTS media DROP COLUMN IF EXISTS reply_ttl_secs;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+-- the table used to be created by hand, settings of chats are kept in it from now on
+CREATE TABLE IF NOT EXISTS chats (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL
+);
+
+ALTER TABLE IF EXISTS media ADD COLUMN IF NOT EXISTS reply_ttl_secs INT CHECK (reply_ttl_secs > 0);
+ALTER TABLE IF EXISTS tags ADD COLUMN IF NOT EXISTS reply_ttl_secs INT CHECK (reply_ttl_secs > 0);
+ALTER TABLE IF EXISTS chats ADD COLUMN IF NOT EXISTS reply_ttl_secs INT CHECK (reply_ttl_secs > 0);
+
+CREATE TABLE IF NOT EXISTS pending_deletions (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    message_id INT NOT NULL,
+    delete_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS media
+    DROP COLUMN IF EXISTS duration_secs,
+    DROP COLUMN IF EXISTS width,
+    DROP COLUMN IF EXISTS height,
+    DROP COLUMN IF EXISTS thumbnail,
+    DROP COLUMN IF EXISTS has_spoiler,
+    DROP COLUMN IF EXISTS protect_content;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+ALTER TABLE IF EXISTS media
+    ADD COLUMN IF NOT EXISTS duration_secs INT CHECK (duration_secs >= 0),
+    ADD COLUMN IF NOT EXISTS width INT CHECK (width > 0),
+    ADD COLUMN IF NOT EXISTS height INT CHECK (height > 0),
+    ADD COLUMN IF NOT EXISTS thumbnail bytea,
+    ADD COLUMN IF NOT EXISTS has_spoiler BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS protect_content BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS feature_reply_options;
+
+ALTER TABLE IF EXISTS tags
+    DROP COLUMN IF EXISTS notify,
+    DROP COLUMN IF EXISTS reply;
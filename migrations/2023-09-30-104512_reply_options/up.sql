@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- NULL means the default: quote the trigger message and send silently
+ALTER TABLE IF EXISTS tags
+    ADD COLUMN IF NOT EXISTS reply BOOLEAN,
+    ADD COLUMN IF NOT EXISTS notify BOOLEAN;
+
+CREATE TABLE IF NOT EXISTS feature_reply_options (
+    id serial PRIMARY KEY,
+    feature_type media_feature_type NOT NULL UNIQUE,
+    reply BOOLEAN,
+    notify BOOLEAN
+);
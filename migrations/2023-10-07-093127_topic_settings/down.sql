@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS topic_settings;
+
+ALTER TABLE IF EXISTS chats
+    DROP COLUMN IF EXISTS cooldown_secs,
+    DROP COLUMN IF EXISTS duplicate_detection_enabled,
+    DROP COLUMN IF EXISTS text_trigger_enabled;
+
+ALTER TABLE IF EXISTS cron_jobs DROP COLUMN IF EXISTS message_thread_id;
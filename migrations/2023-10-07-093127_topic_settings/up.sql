@@ -0,0 +1,21 @@
+-- Your SQL goes here
+
+-- scheduled messages go to the General topic unless a topic is set
+ALTER TABLE IF EXISTS cron_jobs ADD COLUMN IF NOT EXISTS message_thread_id INT;
+
+-- NULL means the setting is inherited: topic <- chat <- bot defaults
+ALTER TABLE IF EXISTS chats
+    ADD COLUMN IF NOT EXISTS text_trigger_enabled BOOLEAN,
+    ADD COLUMN IF NOT EXISTS duplicate_detection_enabled BOOLEAN,
+    ADD COLUMN IF NOT EXISTS cooldown_secs INT CHECK (cooldown_secs >= 0);
+
+-- a configured topic has a cooldown of its own, others share the one of the chat
+CREATE TABLE IF NOT EXISTS topic_settings (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    message_thread_id INT NOT NULL,
+    text_trigger_enabled BOOLEAN,
+    duplicate_detection_enabled BOOLEAN,
+    cooldown_secs INT CHECK (cooldown_secs >= 0),
+    UNIQUE (chat_id, message_thread_id)
+);
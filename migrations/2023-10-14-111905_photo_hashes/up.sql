@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+CREATE TABLE IF NOT EXISTS photo_hashes (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    -- 64-bit difference hash of the photo
+    hash BIGINT NOT NULL,
+    message_url character varying(255) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS photo_hashes_chat_id_idx ON photo_hashes (chat_id);
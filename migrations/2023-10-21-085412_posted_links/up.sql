@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+CREATE TABLE IF NOT EXISTS posted_links (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    -- canonical form of the link, see `canonical_url.rs`
+    url character varying(2048) NOT NULL,
+    message_url character varying(255) NOT NULL,
+    UNIQUE (chat_id, url)
+);
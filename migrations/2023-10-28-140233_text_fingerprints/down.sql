@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS text_fingerprints;
+
+ALTER TABLE IF EXISTS topic_settings DROP COLUMN IF EXISTS text_duplicate_detection_enabled;
+ALTER TABLE IF EXISTS chats DROP COLUMN IF EXISTS text_duplicate_detection_enabled;
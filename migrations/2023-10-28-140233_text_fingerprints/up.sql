@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+-- near-duplicate texts are looked for only where it's enabled
+ALTER TABLE IF EXISTS chats ADD COLUMN IF NOT EXISTS text_duplicate_detection_enabled BOOLEAN;
+ALTER TABLE IF EXISTS topic_settings ADD COLUMN IF NOT EXISTS text_duplicate_detection_enabled BOOLEAN;
+
+CREATE TABLE IF NOT EXISTS text_fingerprints (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    -- 64-bit SimHash of the text
+    fingerprint BIGINT NOT NULL,
+    message_url character varying(255) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS text_fingerprints_chat_id_idx ON text_fingerprints (chat_id);
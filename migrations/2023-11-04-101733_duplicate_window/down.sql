@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS text_fingerprints_created_at_idx;
+DROP INDEX IF EXISTS posted_links_created_at_idx;
+DROP INDEX IF EXISTS photo_hashes_created_at_idx;
+DROP INDEX IF EXISTS forwarded_messages_created_at_idx;
+DROP INDEX IF EXISTS forwarded_messages_lookup_idx;
+
+ALTER TABLE IF EXISTS text_fingerprints DROP COLUMN IF EXISTS created_at;
+ALTER TABLE IF EXISTS posted_links DROP COLUMN IF EXISTS created_at;
+ALTER TABLE IF EXISTS photo_hashes DROP COLUMN IF EXISTS created_at;
+ALTER TABLE IF EXISTS forwarded_messages DROP COLUMN IF EXISTS created_at;
+
+ALTER TABLE IF EXISTS chats DROP COLUMN IF EXISTS duplicate_window_days;
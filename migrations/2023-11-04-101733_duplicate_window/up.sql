@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- only duplicates of messages newer than the window count, older ones are pruned
+ALTER TABLE IF EXISTS chats ADD COLUMN IF NOT EXISTS duplicate_window_days INT CHECK (duplicate_window_days > 0);
+
+-- rows which existed before are considered posted right now
+ALTER TABLE IF EXISTS forwarded_messages ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE IF EXISTS photo_hashes ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE IF EXISTS posted_links ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE IF EXISTS text_fingerprints ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS forwarded_messages_lookup_idx
+    ON forwarded_messages (chat_id, forwarded_chat_id, forwarded_message_id);
+CREATE INDEX IF NOT EXISTS forwarded_messages_created_at_idx ON forwarded_messages (created_at);
+CREATE INDEX IF NOT EXISTS photo_hashes_created_at_idx ON photo_hashes (created_at);
+CREATE INDEX IF NOT EXISTS posted_links_created_at_idx ON posted_links (created_at);
+CREATE INDEX IF NOT EXISTS text_fingerprints_created_at_idx ON text_fingerprints (created_at);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS forwarded_messages DROP CONSTRAINT IF EXISTS forwarded_messages_identity_key;
+
+CREATE INDEX IF NOT EXISTS forwarded_messages_lookup_idx
+    ON forwarded_messages (chat_id, forwarded_chat_id, forwarded_message_id);
+
+-- deleted duplicates can't be restored
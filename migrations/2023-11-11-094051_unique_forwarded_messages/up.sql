@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+-- the earliest registration of a forward is the original one
+DELETE FROM forwarded_messages a
+    USING forwarded_messages b
+    WHERE a.chat_id = b.chat_id
+        AND a.forwarded_chat_id = b.forwarded_chat_id
+        AND a.forwarded_message_id = b.forwarded_message_id
+        AND a.id > b.id;
+
+-- the unique index serves the lookups as well
+DROP INDEX IF EXISTS forwarded_messages_lookup_idx;
+
+ALTER TABLE IF EXISTS forwarded_messages
+    ADD CONSTRAINT forwarded_messages_identity_key
+        UNIQUE (chat_id, forwarded_chat_id, forwarded_message_id);
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+
+-- forwards of messages which are not channel posts have no ids to compare,
+-- so they are identified by the sender, the original date and the content
+CREATE TABLE IF NOT EXISTS forward_fingerprints (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    -- e.g. 'user:42', 'sender:John Doe' or 'chat:-100500'
+    origin character varying(255) NOT NULL,
+    forward_date TIMESTAMPTZ NOT NULL,
+    content_hash BIGINT NOT NULL,
+    message_url character varying(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT forward_fingerprints_identity_key
+        UNIQUE (chat_id, origin, forward_date, content_hash)
+);
+
+CREATE INDEX IF NOT EXISTS forward_fingerprints_created_at_idx ON forward_fingerprints (created_at);
@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS text_fingerprints
+    DROP COLUMN IF EXISTS repost_count,
+    DROP COLUMN IF EXISTS poster_name,
+    DROP COLUMN IF EXISTS poster_id;
+ALTER TABLE IF EXISTS posted_links
+    DROP COLUMN IF EXISTS repost_count,
+    DROP COLUMN IF EXISTS poster_name,
+    DROP COLUMN IF EXISTS poster_id;
+ALTER TABLE IF EXISTS photo_hashes
+    DROP COLUMN IF EXISTS repost_count,
+    DROP COLUMN IF EXISTS poster_name,
+    DROP COLUMN IF EXISTS poster_id;
+ALTER TABLE IF EXISTS forward_fingerprints
+    DROP COLUMN IF EXISTS repost_count,
+    DROP COLUMN IF EXISTS poster_name,
+    DROP COLUMN IF EXISTS poster_id;
+ALTER TABLE IF EXISTS forwarded_messages
+    DROP COLUMN IF EXISTS repost_count,
+    DROP COLUMN IF EXISTS poster_name,
+    DROP COLUMN IF EXISTS poster_id;
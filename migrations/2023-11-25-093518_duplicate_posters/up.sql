@@ -0,0 +1,24 @@
+-- Your SQL goes here
+
+-- who posted the original message first and how many times it has been reposted since then,
+-- posters of rows which existed before are unknown
+ALTER TABLE IF EXISTS forwarded_messages
+    ADD COLUMN IF NOT EXISTS poster_id BIGINT,
+    ADD COLUMN IF NOT EXISTS poster_name character varying(255),
+    ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
+ALTER TABLE IF EXISTS forward_fingerprints
+    ADD COLUMN IF NOT EXISTS poster_id BIGINT,
+    ADD COLUMN IF NOT EXISTS poster_name character varying(255),
+    ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
+ALTER TABLE IF EXISTS photo_hashes
+    ADD COLUMN IF NOT EXISTS poster_id BIGINT,
+    ADD COLUMN IF NOT EXISTS poster_name character varying(255),
+    ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
+ALTER TABLE IF EXISTS posted_links
+    ADD COLUMN IF NOT EXISTS poster_id BIGINT,
+    ADD COLUMN IF NOT EXISTS poster_name character varying(255),
+    ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
+ALTER TABLE IF EXISTS text_fingerprints
+    ADD COLUMN IF NOT EXISTS poster_id BIGINT,
+    ADD COLUMN IF NOT EXISTS poster_name character varying(255),
+    ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
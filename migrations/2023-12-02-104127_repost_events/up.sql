@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+-- every detected duplicate, kept for the repost leaderboard
+CREATE TABLE IF NOT EXISTS repost_events (
+    id serial PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    reposter_id BIGINT,
+    reposter_name character varying(255),
+    message_url character varying(255) NOT NULL,
+    original_url character varying(255) NOT NULL,
+    original_poster_id BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS repost_events_chat_id_created_at_idx ON repost_events (chat_id, created_at);
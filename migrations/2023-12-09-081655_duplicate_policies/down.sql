@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS chats
+    DROP COLUMN IF EXISTS mute_minutes,
+    DROP COLUMN IF EXISTS mute_window_hours,
+    DROP COLUMN IF EXISTS mute_after_duplicates,
+    DROP COLUMN IF EXISTS duplicate_policy;
+
+DROP TYPE IF EXISTS duplicate_policy;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+CREATE TYPE duplicate_policy AS ENUM (
+    'reply',
+    'delete',
+    'escalate'
+);
+
+-- 'reply' mocks duplicates with a media, 'delete' removes them and posts a notice,
+-- 'escalate' warns reposters and mutes them after too many duplicates within the window;
+-- NULL limits fall back to bot defaults;
+-- telegram restricts forever for more than 366 days, so mutes are shorter than that
+ALTER TABLE IF EXISTS chats
+    ADD COLUMN IF NOT EXISTS duplicate_policy duplicate_policy NOT NULL DEFAULT 'reply',
+    ADD COLUMN IF NOT EXISTS mute_after_duplicates INT CHECK (mute_after_duplicates > 0),
+    ADD COLUMN IF NOT EXISTS mute_window_hours INT CHECK (mute_window_hours > 0),
+    ADD COLUMN IF NOT EXISTS mute_minutes INT CHECK (mute_minutes > 0 AND mute_minutes < 366 * 24 * 60);
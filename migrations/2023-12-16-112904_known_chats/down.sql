@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS chats DROP CONSTRAINT IF EXISTS chats_chat_id_key;
+
+ALTER TABLE IF EXISTS chats
+    DROP COLUMN IF EXISTS broadcast_enabled,
+    DROP COLUMN IF EXISTS active,
+    DROP COLUMN IF EXISTS title;
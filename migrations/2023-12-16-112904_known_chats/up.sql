@@ -0,0 +1,16 @@
+-- Your SQL goes here
+
+-- chats are registered by the bot itself from now on:
+-- 'active' is whether the bot is a member, 'broadcast_enabled' opts the chat out of broadcasts
+ALTER TABLE IF EXISTS chats
+    ADD COLUMN IF NOT EXISTS title character varying(255),
+    ADD COLUMN IF NOT EXISTS active BOOLEAN NOT NULL DEFAULT true,
+    ADD COLUMN IF NOT EXISTS broadcast_enabled BOOLEAN NOT NULL DEFAULT true;
+
+-- the earliest settings of a chat win
+DELETE FROM chats a
+    USING chats b
+    WHERE a.chat_id = b.chat_id
+        AND a.id > b.id;
+
+ALTER TABLE IF EXISTS chats ADD CONSTRAINT chats_chat_id_key UNIQUE (chat_id);
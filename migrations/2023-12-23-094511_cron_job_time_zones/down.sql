@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE IF EXISTS chats DROP COLUMN IF EXISTS time_zone;
+ALTER TABLE IF EXISTS cron_jobs DROP COLUMN IF EXISTS time_zone;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+-- IANA names, e.g. 'Europe/Kyiv'; jobs without a zone use the one of their chat or UTC
+ALTER TABLE IF EXISTS cron_jobs ADD COLUMN IF NOT EXISTS time_zone character varying(64);
+ALTER TABLE IF EXISTS chats ADD COLUMN IF NOT EXISTS time_zone character varying(64);
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+
+DELETE FROM media_to_cron_job WHERE cron_job_id IN (SELECT id FROM cron_jobs WHERE pattern IS NULL);
+DELETE FROM cron_jobs WHERE pattern IS NULL;
+
+ALTER TABLE IF EXISTS cron_jobs
+    DROP CONSTRAINT IF EXISTS cron_jobs_pattern_or_run_at_check,
+    DROP COLUMN IF EXISTS skipped_at,
+    DROP COLUMN IF EXISTS sent_at,
+    DROP COLUMN IF EXISTS run_at,
+    ALTER COLUMN pattern SET NOT NULL;
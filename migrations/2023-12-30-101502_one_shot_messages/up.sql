@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- a job either recurs by its pattern or is sent once at 'run_at', after which 'sent_at' is set,
+-- or 'skipped_at' if the bot has missed the time
+ALTER TABLE IF EXISTS cron_jobs
+    ALTER COLUMN pattern DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS run_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS sent_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS skipped_at TIMESTAMPTZ,
+    ADD CONSTRAINT cron_jobs_pattern_or_run_at_check CHECK ((pattern IS NULL) <> (run_at IS NULL));
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER IF EXISTS feature_reply_options_notify_change ON feature_reply_options;
+DROP TRIGGER IF EXISTS media_to_feature_notify_change ON media_to_feature;
+DROP TRIGGER IF EXISTS media_script_steps_notify_change ON media_script_steps;
+DROP TRIGGER IF EXISTS media_group_items_notify_change ON media_group_items;
+DROP TRIGGER IF EXISTS media_notify_change ON media;
+DROP TRIGGER IF EXISTS tag_to_media_notify_change ON tag_to_media;
+DROP TRIGGER IF EXISTS tags_notify_change ON tags;
+DROP TRIGGER IF EXISTS topic_settings_notify_change ON topic_settings;
+DROP TRIGGER IF EXISTS chats_notify_change ON chats;
+DROP TRIGGER IF EXISTS cron_jobs_notify_change ON cron_jobs;
+
+DROP FUNCTION IF EXISTS notify_table_change();
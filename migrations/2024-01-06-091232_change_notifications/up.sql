@@ -0,0 +1,57 @@
+-- Your SQL goes here
+
+-- lets the bot resync cron jobs and drop cached settings right after they are edited,
+-- the payload is the name of the changed table
+CREATE OR REPLACE FUNCTION notify_table_change() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('table_changes', TG_TABLE_NAME);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER cron_jobs_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON cron_jobs
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+-- the bot itself keeps titles and membership of chats up to date, those are not worth a notification
+CREATE TRIGGER chats_notify_change
+    AFTER INSERT OR DELETE OR UPDATE OF chat_id, reply_ttl_secs, text_trigger_enabled,
+        duplicate_detection_enabled, cooldown_secs, text_duplicate_detection_enabled,
+        duplicate_window_days, duplicate_policy, mute_after_duplicates, mute_window_hours,
+        mute_minutes, broadcast_enabled, time_zone ON chats
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER topic_settings_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON topic_settings
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER tags_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON tags
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER tag_to_media_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON tag_to_media
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+-- as well as the file ids telegram gives to uploaded media
+CREATE TRIGGER media_notify_change
+    AFTER INSERT OR DELETE OR UPDATE OF name, type, data, storage, storage_key, caption,
+        parse_mode, payload, reply_ttl_secs, duration_secs, width, height, thumbnail,
+        has_spoiler, protect_content ON media
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER media_group_items_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON media_group_items
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER media_script_steps_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON media_script_steps
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER media_to_feature_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON media_to_feature
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
+
+CREATE TRIGGER feature_reply_options_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON feature_reply_options
+    FOR EACH ROW EXECUTE FUNCTION notify_table_change();
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use deadpool::managed::Pool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use std::env;
+
+use krusty::database::repository::AsyncRepository;
+use krusty::database::types::{NewCronJob, TextParseMode};
+use krusty::ingest::ingest_cron_job;
+
+const USAGE: &str = "Usage: ingest_cron_job <media name>... \
+    (--pattern <cron pattern> | --run-at <rfc3339 time>) [--chat-id <id>] [--thread-id <id>] \
+    [--caption <text>] [--parse-mode none|markdown_v2|html] [--description <text>] \
+    [--time-zone <iana name>]";
+
+fn parse_parse_mode(s: &str) -> TextParseMode {
+    match s.to_lowercase().as_str() {
+        "none" => TextParseMode::None,
+        "markdown_v2" => TextParseMode::MarkdownV2,
+        "html" => TextParseMode::Html,
+        unknown => panic!("Unrecognized parse mode: '{unknown}'. {USAGE}"),
+    }
+}
+
+/// Validates and adds a cron job sending one of the given media, so that malformed jobs
+/// never reach the bot. Uses DATABASE_URL as the bot itself.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut media_names = Vec::new();
+    let mut job = NewCronJob {
+        pattern: None,
+        run_at: None,
+        chat_id: None,
+        message_thread_id: None,
+        caption: None,
+        description: None,
+        parse_mode: TextParseMode::None,
+        time_zone: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{USAGE}"));
+        match arg.as_str() {
+            "--pattern" => job.pattern = Some(value()),
+            "--run-at" => {
+                job.run_at = Some(DateTime::parse_from_rfc3339(&value())?.with_timezone(&Utc))
+            }
+            "--chat-id" => job.chat_id = Some(value().parse()?),
+            "--thread-id" => job.message_thread_id = Some(value().parse()?),
+            "--caption" => job.caption = Some(value()),
+            "--parse-mode" => job.parse_mode = parse_parse_mode(&value()),
+            "--description" => job.description = Some(value()),
+            "--time-zone" => job.time_zone = Some(value()),
+            _ => media_names.push(arg),
+        }
+    }
+
+    if media_names.is_empty() {
+        panic!("{USAGE}");
+    }
+
+    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+    let mng = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(mng).build()?;
+
+    let mut repository = AsyncRepository::new(pool);
+    let id = ingest_cron_job(&mut repository, job, &media_names).await?;
+    println!("Added cron job with id {id}");
+
+    Ok(())
+}
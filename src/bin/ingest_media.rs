@@ -0,0 +1,123 @@
+use deadpool::managed::Pool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use std::env;
+
+use krusty::database::repository::AsyncRepository;
+use krusty::database::types::{
+    MediaMetadata, MediaStorage, MediaType, NewMedia, NewMediaGroupItem, TextParseMode,
+};
+use krusty::ingest::ingest_media;
+use krusty::storage::MediaStores;
+
+const USAGE: &str = "Usage: ingest_media <name> <type> [file] \
+    [--caption <text>] [--parse-mode none|markdown_v2|html] [--payload <json>] \
+    [--duration <secs>] [--width <px>] [--height <px>] [--thumbnail <jpeg file>] \
+    [--spoiler] [--protect-content] [--storage postgres|filesystem|s3] \
+    [--item <media name> [--item-caption <text>]]...";
+
+fn parse_type(s: &str) -> MediaType {
+    match s.to_lowercase().as_str() {
+        "voice" => MediaType::Voice,
+        "video" => MediaType::Video,
+        "picture" => MediaType::Picture,
+        "animation" => MediaType::Animation,
+        "plain_text" => MediaType::PlainText,
+        "document" => MediaType::Document,
+        "video_note" => MediaType::VideoNote,
+        "sticker" => MediaType::Sticker,
+        "album" => MediaType::Album,
+        "script" => MediaType::Script,
+        "audio" => MediaType::Audio,
+        "location" => MediaType::Location,
+        "venue" => MediaType::Venue,
+        "dice" => MediaType::Dice,
+        "poll" => MediaType::Poll,
+        "contact" => MediaType::Contact,
+        unknown => panic!("Unrecognized media type: '{unknown}'. {USAGE}"),
+    }
+}
+
+fn parse_parse_mode(s: &str) -> TextParseMode {
+    match s.to_lowercase().as_str() {
+        "none" => TextParseMode::None,
+        "markdown_v2" => TextParseMode::MarkdownV2,
+        "html" => TextParseMode::Html,
+        unknown => panic!("Unrecognized parse mode: '{unknown}'. {USAGE}"),
+    }
+}
+
+fn parse_storage(s: &str) -> MediaStorage {
+    match s.to_lowercase().as_str() {
+        "postgres" => MediaStorage::Postgres,
+        "filesystem" => MediaStorage::Filesystem,
+        "s3" => MediaStorage::S3,
+        unknown => panic!("Unrecognized media storage: '{unknown}'. {USAGE}"),
+    }
+}
+
+/// Validates and adds a media, configured the same way as the bot itself,
+/// i.e. with DATABASE_URL, MEDIA_FS_ROOT and MEDIA_S3_* variables.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut positional = Vec::new();
+    let mut caption = None;
+    let mut payload = None;
+    let mut metadata = MediaMetadata::default();
+    let mut parse_mode = TextParseMode::None;
+    let mut storage = MediaStorage::Postgres;
+    let mut items: Vec<NewMediaGroupItem> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{USAGE}"));
+        match arg.as_str() {
+            "--caption" => caption = Some(value()),
+            "--parse-mode" => parse_mode = parse_parse_mode(&value()),
+            "--payload" => payload = Some(serde_json::from_str(&value())?),
+            "--duration" => metadata.duration_secs = Some(value().parse()?),
+            "--width" => metadata.width = Some(value().parse()?),
+            "--height" => metadata.height = Some(value().parse()?),
+            "--thumbnail" => metadata.thumbnail = Some(std::fs::read(value())?),
+            "--spoiler" => metadata.has_spoiler = true,
+            "--protect-content" => metadata.protect_content = true,
+            "--storage" => storage = parse_storage(&value()),
+            "--item" => items.push(NewMediaGroupItem {
+                name: value(),
+                caption: None,
+            }),
+            "--item-caption" => {
+                items
+                    .last_mut()
+                    .unwrap_or_else(|| panic!("{USAGE}"))
+                    .caption = Some(value())
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if !(2..=3).contains(&positional.len()) {
+        panic!("{USAGE}");
+    }
+    let data = positional.get(2).map(std::fs::read).transpose()?;
+
+    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+    let mng = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(mng).build()?;
+
+    let mut repository = AsyncRepository::new(pool);
+    let stores = MediaStores::from_env(repository.clone())?;
+
+    let media = NewMedia {
+        name: positional[0].clone(),
+        type_: parse_type(&positional[1]),
+        data,
+        caption,
+        parse_mode,
+        payload,
+        metadata,
+    };
+    let id = ingest_media(&mut repository, &stores, media, &items, storage).await?;
+    println!("Added media with id {id}");
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use deadpool::managed::Pool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use std::env;
+
+use krusty::database::repository::AsyncRepository;
+use krusty::database::types::MediaStorage;
+use krusty::storage::{move_media, MediaStores};
+
+const USAGE: &str =
+    "Usage: migrate_media <from> <to>, where storages are: postgres, filesystem, s3";
+
+fn parse_storage(s: &str) -> MediaStorage {
+    match s.to_lowercase().as_str() {
+        "postgres" => MediaStorage::Postgres,
+        "filesystem" => MediaStorage::Filesystem,
+        "s3" => MediaStorage::S3,
+        unknown => panic!("Unrecognized media storage: '{unknown}'. {USAGE}"),
+    }
+}
+
+/// Moves media blobs between storages configured the same way as for the bot itself,
+/// i.e. with DATABASE_URL, MEDIA_FS_ROOT and MEDIA_S3_* variables.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        panic!("{USAGE}");
+    }
+    let from = parse_storage(&args[0]);
+    let to = parse_storage(&args[1]);
+
+    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+    let mng = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
+    let pool = Pool::builder(mng).build()?;
+
+    let mut repository = AsyncRepository::new(pool);
+    let stores = MediaStores::from_env(repository.clone())?;
+
+    let moved = move_media(&mut repository, &stores, from, to).await?;
+    println!("Moved {moved} media from {from:?} to {to:?}");
+
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use cached::Cached;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// LRU cache bounded by the total size of stored values in bytes rather than by their count.
+/// Values larger than the per-item ceiling (or the whole budget) are never cached.
+pub struct ByteBudgetCache<K, V> {
+    store: HashMap<K, (V, u64)>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    bytes: usize,
+    max_bytes: usize,
+    max_item_bytes: Option<usize>,
+    hits: u64,
+    misses: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: AsRef<[u8]>> ByteBudgetCache<K, V> {
+    pub fn new(max_bytes: usize, max_item_bytes: Option<usize>) -> Self {
+        ByteBudgetCache {
+            store: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            max_bytes,
+            max_item_bytes,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn cache_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn is_cacheable(&self, v: &V) -> bool {
+        let len = v.as_ref().len();
+        len <= self.max_bytes && self.max_item_bytes.is_none_or(|max| len <= max)
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn touch<Q>(&mut self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let tick = self.next_tick();
+        match self.store.get_mut(k) {
+            Some((_, t)) => {
+                let key = self.order.remove(t).expect("LRU order is out of sync");
+                *t = tick;
+                self.order.insert(tick, key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn evict(&mut self, keep: &K) {
+        while self.bytes > self.max_bytes {
+            let oldest = self.order.values().find(|k| *k != keep).cloned();
+            match oldest {
+                Some(k) => {
+                    self.cache_remove(&k);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: AsRef<[u8]>> Cached<K, V> for ByteBudgetCache<K, V> {
+    fn cache_get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.touch(k) {
+            self.hits += 1;
+            self.store.get(k).map(|(v, _)| v)
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    fn cache_get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.touch(k) {
+            self.hits += 1;
+            self.store.get_mut(k).map(|(v, _)| v)
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    fn cache_set(&mut self, k: K, v: V) -> Option<V> {
+        let previous = self.cache_remove(&k);
+        if !self.is_cacheable(&v) {
+            return previous;
+        }
+
+        let tick = self.next_tick();
+        self.bytes += v.as_ref().len();
+        self.order.insert(tick, k.clone());
+        self.store.insert(k.clone(), (v, tick));
+        self.evict(&k);
+
+        previous
+    }
+
+    // Unlike `cache_set`, the value is kept even if it is above the ceiling,
+    // since a reference to it has to be returned.
+    fn cache_get_or_set_with<F: FnOnce() -> V>(&mut self, k: K, f: F) -> &mut V {
+        if self.touch(&k) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            let v = f();
+            let tick = self.next_tick();
+            self.bytes += v.as_ref().len();
+            self.order.insert(tick, k.clone());
+            self.store.insert(k.clone(), (v, tick));
+            self.evict(&k);
+        }
+
+        &mut self.store.get_mut(&k).unwrap().0
+    }
+
+    fn cache_remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.store.remove(k).map(|(v, tick)| {
+            self.order.remove(&tick);
+            self.bytes -= v.as_ref().len();
+            v
+        })
+    }
+
+    fn cache_clear(&mut self) {
+        self.store.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+
+    fn cache_reset(&mut self) {
+        self.store = HashMap::new();
+        self.order = BTreeMap::new();
+        self.bytes = 0;
+    }
+
+    fn cache_reset_metrics(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    fn cache_size(&self) -> usize {
+        self.store.len()
+    }
+
+    fn cache_hits(&self) -> Option<u64> {
+        Some(self.hits)
+    }
+
+    fn cache_misses(&self) -> Option<u64> {
+        Some(self.misses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cached::Cached;
+
+    use super::ByteBudgetCache;
+
+    fn bytes(n: usize) -> Vec<u8> {
+        vec![0; n]
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let mut cache = ByteBudgetCache::new(10, None);
+        cache.cache_set("a", bytes(4));
+        cache.cache_set("b", bytes(4));
+        cache.cache_get("a");
+        cache.cache_set("c", bytes(4));
+
+        assert!(cache.cache_get("a").is_some());
+        assert!(cache.cache_get("b").is_none());
+        assert!(cache.cache_get("c").is_some());
+        assert_eq!(cache.cache_bytes(), 8);
+    }
+
+    #[test]
+    fn test_several_items_are_evicted_for_big_one() {
+        let mut cache = ByteBudgetCache::new(10, None);
+        cache.cache_set("a", bytes(3));
+        cache.cache_set("b", bytes(3));
+        cache.cache_set("c", bytes(3));
+        cache.cache_set("d", bytes(9));
+
+        assert_eq!(cache.cache_size(), 1);
+        assert_eq!(cache.cache_bytes(), 9);
+    }
+
+    #[test]
+    fn test_items_above_ceiling_are_not_cached() {
+        let mut cache = ByteBudgetCache::new(100, Some(10));
+        cache.cache_set("a", bytes(5));
+        cache.cache_set("b", bytes(11));
+        cache.cache_set("c", bytes(101));
+
+        assert_eq!(cache.cache_size(), 1);
+        assert!(cache.cache_get("b").is_none());
+        assert!(cache.cache_get("c").is_none());
+    }
+
+    #[test]
+    fn test_replacing_value_updates_size() {
+        let mut cache = ByteBudgetCache::new(100, Some(10));
+        cache.cache_set("a", bytes(5));
+        assert_eq!(cache.cache_set("a", bytes(7)), Some(bytes(5)));
+        assert_eq!(cache.cache_bytes(), 7);
+
+        // too big replacement drops the cached value
+        cache.cache_set("a", bytes(70));
+        assert_eq!(cache.cache_bytes(), 0);
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let mut cache = ByteBudgetCache::new(100, None);
+        cache.cache_set("a", bytes(5));
+        cache.cache_get("a");
+        cache.cache_get("a");
+        cache.cache_get("b");
+
+        assert_eq!(cache.cache_hits(), Some(2));
+        assert_eq!(cache.cache_misses(), Some(1));
+    }
+}
@@ -1,8 +1,29 @@
 use cached::proc_macro::cached;
-use cached::{SizedCache, TimedCache, TimedSizedCache};
+use cached::{Cached, SizedCache, TimedCache, TimedSizedCache};
+use std::fmt;
 use tracing_attributes::instrument;
 
 use crate::database::{repository::AsyncRepository, types};
+use crate::storage::MediaStores;
+
+use super::byte_budget_cache::ByteBudgetCache;
+
+pub struct MediaCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub items: usize,
+    pub bytes: usize,
+}
+
+impl fmt::Display for MediaCacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hits: {}, misses: {}, items: {}, bytes: {}",
+            self.hits, self.misses, self.items, self.bytes
+        )
+    }
+}
 
 #[instrument(level = "trace", skip(r))]
 #[cached(
@@ -29,15 +50,255 @@ pub async fn media_info_by_tag_text(
     r.media_info_by_tag_text(t).await
 }
 
+#[instrument(level = "trace", skip(r, s))]
+#[cached(
+    type = "ByteBudgetCache<String, Vec<u8>>",
+    create = "{ ByteBudgetCache::new(64 * 1024 * 1024, None) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_data_by_name(
+    r: &mut AsyncRepository,
+    s: &MediaStores,
+    n: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let location = r.media_location_by_name(n).await?;
+    s.load(&location).await
+}
+
+pub async fn configure_media_data_cache(max_bytes: usize, max_item_bytes: Option<usize>) {
+    *MEDIA_DATA_BY_NAME.lock().await = ByteBudgetCache::new(max_bytes, max_item_bytes);
+}
+
+pub async fn media_data_cache_stats() -> MediaCacheStats {
+    let cache = MEDIA_DATA_BY_NAME.lock().await;
+    MediaCacheStats {
+        hits: cache.cache_hits().unwrap_or_default(),
+        misses: cache.cache_misses().unwrap_or_default(),
+        items: cache.cache_size(),
+        bytes: cache.cache_bytes(),
+    }
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "SizedCache<String, Option<String>>",
+    create = "{ SizedCache::with_size(1000) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_file_id_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<Option<String>> {
+    r.media_file_id_by_name(n).await
+}
+
+#[instrument(level = "trace", skip(r))]
+pub async fn set_media_file_id(
+    r: &mut AsyncRepository,
+    n: &str,
+    file_id: Option<String>,
+) -> anyhow::Result<()> {
+    r.update_media_file_id(n, file_id.as_deref()).await?;
+    MEDIA_FILE_ID_BY_NAME
+        .lock()
+        .await
+        .cache_set(n.to_string(), file_id);
+
+    Ok(())
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, Option<serde_json::Value>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_payload_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    r.media_payload_by_name(n).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, types::MediaMetadata>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_metadata_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<types::MediaMetadata> {
+    r.media_metadata_by_name(n).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, Vec<types::MediaGroupItem>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(50, 3600) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_group_items_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<Vec<types::MediaGroupItem>> {
+    r.media_group_items_by_name(n).await
+}
+
 #[instrument(level = "trace", skip(r))]
 #[cached(
-    type = "SizedCache<String, Vec<u8>>",
-    create = "{ SizedCache::with_size(100) }",
+    type = "TimedSizedCache<String, Vec<types::ScriptStep>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(50, 3600) }",
+    result = true,
+    convert = r#"{ format!("{n}") }"#
+)]
+pub async fn media_script_steps_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<Vec<types::ScriptStep>> {
+    r.media_script_steps_by_name(n).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, Option<i32>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
     result = true,
     convert = r#"{ format!("{n}") }"#
 )]
-pub async fn media_data_by_name(r: &mut AsyncRepository, n: &str) -> anyhow::Result<Vec<u8>> {
-    r.media_data_by_name(n).await
+pub async fn media_reply_ttl_by_name(
+    r: &mut AsyncRepository,
+    n: &str,
+) -> anyhow::Result<Option<i32>> {
+    r.media_reply_ttl_by_name(n).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, Option<i32>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = r#"{ format!("{t}") }"#
+)]
+pub async fn tag_reply_ttl_by_text(
+    r: &mut AsyncRepository,
+    t: &str,
+) -> anyhow::Result<Option<i32>> {
+    r.tag_reply_ttl_by_text(t).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<i64, Option<i32>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ c }"
+)]
+pub async fn chat_reply_ttl_by_id(r: &mut AsyncRepository, c: i64) -> anyhow::Result<Option<i32>> {
+    r.chat_reply_ttl_by_id(c).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<i64, Option<i32>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ c }"
+)]
+pub async fn chat_duplicate_window_by_id(
+    r: &mut AsyncRepository,
+    c: i64,
+) -> anyhow::Result<Option<i32>> {
+    r.chat_duplicate_window_by_id(c).await
+}
+
+/// Registers the chat at most once in a while, since every message in it is a reminder.
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<i64, ()>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ c }"
+)]
+pub async fn remember_chat(r: &mut AsyncRepository, c: i64, t: Option<&str>) -> anyhow::Result<()> {
+    r.upsert_chat(c, t, true).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<i64, Option<types::ModerationSettings>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ c }"
+)]
+pub async fn chat_moderation_settings_by_id(
+    r: &mut AsyncRepository,
+    c: i64,
+) -> anyhow::Result<Option<types::ModerationSettings>> {
+    r.chat_moderation_settings_by_id(c).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<i64, Option<types::ChatSettings>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ c }"
+)]
+pub async fn chat_settings_by_id(
+    r: &mut AsyncRepository,
+    c: i64,
+) -> anyhow::Result<Option<types::ChatSettings>> {
+    r.chat_settings_by_id(c).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<(i64, i32), Option<types::ChatSettings>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = "{ (c, t) }"
+)]
+pub async fn topic_settings_by_ids(
+    r: &mut AsyncRepository,
+    c: i64,
+    t: i32,
+) -> anyhow::Result<Option<types::ChatSettings>> {
+    r.topic_settings_by_ids(c, t).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedSizedCache<String, types::ReplyOptions>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3600) }",
+    result = true,
+    convert = r#"{ format!("{t}") }"#
+)]
+pub async fn tag_reply_options_by_text(
+    r: &mut AsyncRepository,
+    t: &str,
+) -> anyhow::Result<types::ReplyOptions> {
+    r.tag_reply_options_by_text(t).await
+}
+
+#[instrument(level = "trace", skip(r))]
+#[cached(
+    type = "TimedCache<String, types::ReplyOptions>",
+    create = "{ TimedCache::with_lifespan(3600) }",
+    result = true,
+    convert = r#"{ format!("{:?}", t) }"#
+)]
+pub async fn feature_reply_options(
+    r: &mut AsyncRepository,
+    t: types::MediaFeatureType,
+) -> anyhow::Result<types::ReplyOptions> {
+    r.feature_reply_options(t).await
 }
 
 #[instrument(level = "trace", skip(r))]
@@ -53,3 +314,74 @@ pub async fn media_info_by_feature_type(
 ) -> anyhow::Result<Vec<types::MediaInfo>> {
     r.media_info_by_feature_type(t).await
 }
+
+/// Drops cached values read from the table, so that edits of it apply right away.
+pub async fn invalidate(table: &str) {
+    match table {
+        "chats" => {
+            CHAT_REPLY_TTL_BY_ID.lock().await.cache_clear();
+            CHAT_DUPLICATE_WINDOW_BY_ID.lock().await.cache_clear();
+            CHAT_MODERATION_SETTINGS_BY_ID.lock().await.cache_clear();
+            CHAT_SETTINGS_BY_ID.lock().await.cache_clear();
+        }
+        "topic_settings" => TOPIC_SETTINGS_BY_IDS.lock().await.cache_clear(),
+        "tags" | "tag_to_media" => {
+            TAGS.lock().await.cache_clear();
+            MEDIA_INFO_BY_TAG_TEXT.lock().await.cache_clear();
+            TAG_REPLY_TTL_BY_TEXT.lock().await.cache_clear();
+            TAG_REPLY_OPTIONS_BY_TEXT.lock().await.cache_clear();
+        }
+        "media" => {
+            MEDIA_INFO_BY_TAG_TEXT.lock().await.cache_clear();
+            MEDIA_INFO_BY_FEATURE_TYPE.lock().await.cache_clear();
+            MEDIA_DATA_BY_NAME.lock().await.cache_clear();
+            MEDIA_FILE_ID_BY_NAME.lock().await.cache_clear();
+            MEDIA_PAYLOAD_BY_NAME.lock().await.cache_clear();
+            MEDIA_METADATA_BY_NAME.lock().await.cache_clear();
+            MEDIA_REPLY_TTL_BY_NAME.lock().await.cache_clear();
+            MEDIA_GROUP_ITEMS_BY_NAME.lock().await.cache_clear();
+            MEDIA_SCRIPT_STEPS_BY_NAME.lock().await.cache_clear();
+        }
+        "media_group_items" => MEDIA_GROUP_ITEMS_BY_NAME.lock().await.cache_clear(),
+        "media_script_steps" => MEDIA_SCRIPT_STEPS_BY_NAME.lock().await.cache_clear(),
+        "media_to_feature" => MEDIA_INFO_BY_FEATURE_TYPE.lock().await.cache_clear(),
+        "feature_reply_options" => FEATURE_REPLY_OPTIONS.lock().await.cache_clear(),
+        _ => {}
+    }
+}
+
+/// Drops every cached value read from the database, when some changes may have been missed.
+pub async fn invalidate_all() {
+    for table in [
+        "chats",
+        "topic_settings",
+        "tags",
+        "media",
+        "feature_reply_options",
+    ] {
+        invalidate(table).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cached::Cached;
+
+    use super::{invalidate, TAGS, TOPIC_SETTINGS_BY_IDS};
+
+    #[tokio::test]
+    async fn test_invalidate_clears_caches_of_table() {
+        TAGS.lock().await.cache_set(String::default(), vec![]);
+        TOPIC_SETTINGS_BY_IDS.lock().await.cache_set((1, 2), None);
+
+        invalidate("tag_to_media").await;
+
+        assert_eq!(TAGS.lock().await.cache_size(), 0);
+        assert_eq!(TOPIC_SETTINGS_BY_IDS.lock().await.cache_size(), 1);
+
+        invalidate("pending_deletions").await;
+        invalidate("topic_settings").await;
+
+        assert_eq!(TOPIC_SETTINGS_BY_IDS.lock().await.cache_size(), 0);
+    }
+}
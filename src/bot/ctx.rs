@@ -3,18 +3,23 @@ use deadpool::managed::Pool;
 use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use percentage::{PercentageDecimal, PercentageInteger};
 use std::collections::HashMap;
-use teloxide::prelude::*;
 use tokio::sync::Mutex;
 
 use crate::database::repository::AsyncRepository;
+use crate::storage::MediaStores;
+
+use super::features::dupl_checker::DuplicateConfig;
+use super::settings::CooldownKey;
 
 pub struct Ctx {
-    pub text_trigger_timestamps: Mutex<HashMap<ChatId, DateTime<Utc>>>,
-    pub duplicate_forward_timestamps: Mutex<HashMap<ChatId, DateTime<Utc>>>,
+    pub text_trigger_timestamps: Mutex<HashMap<CooldownKey, DateTime<Utc>>>,
+    pub duplicate_forward_timestamps: Mutex<HashMap<CooldownKey, DateTime<Utc>>>,
     pub media_timeout: Duration,
     pub media_being_sent_chance: PercentageInteger,
     pub similarity_threshold: PercentageDecimal,
+    pub duplicates: DuplicateConfig,
     pub repository: AsyncRepository,
+    pub media_stores: MediaStores,
 }
 
 impl Ctx {
@@ -22,7 +27,9 @@ impl Ctx {
         media_timeout: Duration,
         media_being_sent_chance: PercentageInteger,
         similarity_threshold: PercentageDecimal,
+        duplicates: DuplicateConfig,
         pool: Pool<AsyncDieselConnectionManager<diesel_async::AsyncPgConnection>>,
+        media_stores: MediaStores,
     ) -> Self {
         Ctx {
             text_trigger_timestamps: Default::default(),
@@ -30,7 +37,9 @@ impl Ctx {
             media_timeout,
             media_being_sent_chance,
             similarity_threshold,
+            duplicates,
             repository: AsyncRepository::new(pool),
+            media_stores,
         }
     }
 }
@@ -0,0 +1,216 @@
+use chrono::{prelude::*, Duration};
+use teloxide::types::MessageId;
+use teloxide::{prelude::*, ApiError, Bot, RequestError};
+
+use crate::bot::cache::{chat_reply_ttl_by_id, media_reply_ttl_by_name, tag_reply_ttl_by_text};
+use crate::database::repository::AsyncRepository;
+use crate::database::types::{NewPendingDeletion, PendingDeletion};
+
+const DELETION_ATTEMPTS: u32 = 5;
+const FIRST_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Schedules deletion of the reply if a time-to-live is configured for it.
+pub async fn delete_reply_later(
+    bot: Bot,
+    repository: &mut AsyncRepository,
+    chat_id: ChatId,
+    message_ids: &[MessageId],
+    media_name: &str,
+    tag: Option<&str>,
+) -> anyhow::Result<()> {
+    if message_ids.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(ttl) = reply_ttl(repository, media_name, tag, chat_id).await? {
+        delete_later(bot, repository, chat_id, message_ids, ttl).await?;
+    }
+
+    Ok(())
+}
+
+// The time-to-live of the media wins over the one of the tag,
+// which in turn wins over the one of the chat.
+async fn reply_ttl(
+    repository: &mut AsyncRepository,
+    media_name: &str,
+    tag: Option<&str>,
+    chat_id: ChatId,
+) -> anyhow::Result<Option<Duration>> {
+    let media_ttl = media_reply_ttl_by_name(repository, media_name).await?;
+    let tag_ttl = match tag {
+        Some(tag) => tag_reply_ttl_by_text(repository, tag).await?,
+        None => None,
+    };
+    let chat_ttl = chat_reply_ttl_by_id(repository, chat_id.0).await?;
+
+    Ok(choose_ttl(media_ttl, tag_ttl, chat_ttl))
+}
+
+fn choose_ttl(
+    media_ttl: Option<i32>,
+    tag_ttl: Option<i32>,
+    chat_ttl: Option<i32>,
+) -> Option<Duration> {
+    media_ttl
+        .or(tag_ttl)
+        .or(chat_ttl)
+        .filter(|x| *x > 0)
+        .map(|x| Duration::seconds(x.into()))
+}
+
+// Deletions are persisted first, so they are carried out even after restart.
+async fn delete_later(
+    bot: Bot,
+    repository: &mut AsyncRepository,
+    chat_id: ChatId,
+    message_ids: &[MessageId],
+    ttl: Duration,
+) -> anyhow::Result<()> {
+    let delete_at = Utc::now() + ttl;
+    for message_id in message_ids {
+        let deletion = NewPendingDeletion {
+            chat_id: chat_id.0,
+            message_id: message_id.0,
+            delete_at,
+        };
+        let id = repository.insert_pending_deletion(&deletion).await?;
+        spawn_deletion(
+            bot.clone(),
+            repository.clone(),
+            PendingDeletion {
+                id,
+                chat_id: deletion.chat_id,
+                message_id: deletion.message_id,
+                delete_at,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Schedules deletions persisted before the restart, overdue ones are carried out right away.
+pub async fn reschedule_pending_deletions(
+    bot: Bot,
+    mut repository: AsyncRepository,
+) -> anyhow::Result<usize> {
+    let deletions = repository.pending_deletions().await?;
+    let count = deletions.len();
+    for deletion in deletions {
+        spawn_deletion(bot.clone(), repository.clone(), deletion);
+    }
+
+    Ok(count)
+}
+
+// Failed deletions are retried a few times, then they are left pending until the next start.
+fn spawn_deletion(bot: Bot, mut repository: AsyncRepository, deletion: PendingDeletion) {
+    tokio::spawn(async move {
+        tokio::time::sleep(time_left(&deletion.delete_at, &Utc::now())).await;
+        for attempt in 1..=DELETION_ATTEMPTS {
+            let Err(e) = delete_message(&bot, &mut repository, &deletion).await else {
+                return;
+            };
+            log::error!(
+                "Failed to delete message '{}' in chat '{}', attempt {attempt}/{DELETION_ATTEMPTS}: '{e}'",
+                deletion.message_id,
+                deletion.chat_id
+            );
+            if attempt < DELETION_ATTEMPTS {
+                tokio::time::sleep(retry_delay(attempt, &e)).await;
+            }
+        }
+    });
+}
+
+fn retry_delay(attempt: u32, error: &anyhow::Error) -> std::time::Duration {
+    match error.downcast_ref::<RequestError>() {
+        Some(RequestError::RetryAfter(delay)) => *delay,
+        _ => FIRST_RETRY_DELAY * 2u32.pow(attempt - 1),
+    }
+}
+
+fn time_left(delete_at: &DateTime<Utc>, now: &DateTime<Utc>) -> std::time::Duration {
+    (*delete_at - *now).to_std().unwrap_or_default()
+}
+
+async fn delete_message(
+    bot: &Bot,
+    repository: &mut AsyncRepository,
+    deletion: &PendingDeletion,
+) -> anyhow::Result<()> {
+    match bot
+        .delete_message(ChatId(deletion.chat_id), MessageId(deletion.message_id))
+        .await
+    {
+        Ok(_) => {}
+        // there is nothing to retry if the message is gone or too old to be deleted
+        Err(RequestError::Api(ApiError::MessageToDeleteNotFound))
+        | Err(RequestError::Api(ApiError::MessageCantBeDeleted)) => {
+            log::warn!(
+                "Message '{}' in chat '{}' can't be deleted anymore",
+                deletion.message_id,
+                deletion.chat_id
+            );
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    repository.delete_pending_deletion(deletion.id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{prelude::*, Duration};
+
+    use teloxide::RequestError;
+
+    use super::{choose_ttl, retry_delay, time_left};
+
+    #[test]
+    fn test_ttl_precedence() {
+        assert_eq!(
+            choose_ttl(Some(10), Some(20), Some(30)),
+            Some(Duration::seconds(10))
+        );
+        assert_eq!(
+            choose_ttl(None, Some(20), Some(30)),
+            Some(Duration::seconds(20))
+        );
+        assert_eq!(
+            choose_ttl(None, None, Some(30)),
+            Some(Duration::seconds(30))
+        );
+        assert_eq!(choose_ttl(None, None, None), None);
+    }
+
+    #[test]
+    fn test_non_positive_ttl_is_ignored() {
+        assert_eq!(choose_ttl(Some(0), Some(20), None), None);
+        assert_eq!(choose_ttl(Some(-5), None, None), None);
+    }
+
+    #[test]
+    fn test_time_left() {
+        let now = Utc::now();
+        assert_eq!(
+            time_left(&(now + Duration::seconds(90)), &now),
+            std::time::Duration::from_secs(90)
+        );
+        assert_eq!(
+            time_left(&(now - Duration::seconds(90)), &now),
+            std::time::Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let network = anyhow::anyhow!("connection reset");
+        assert_eq!(retry_delay(1, &network), std::time::Duration::from_secs(10));
+        assert_eq!(retry_delay(3, &network), std::time::Duration::from_secs(40));
+
+        let flood = RequestError::RetryAfter(std::time::Duration::from_secs(7)).into();
+        assert_eq!(retry_delay(3, &flood), std::time::Duration::from_secs(7));
+    }
+}
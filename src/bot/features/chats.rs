@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use teloxide::prelude::*;
+use teloxide::types::ChatMemberUpdated;
+
+use crate::bot::{cache::remember_chat, ctx::Ctx};
+
+/// Remembers the chat of the message, so that it gets broadcast messages.
+pub async fn remember_chat_of_message(message: Message, ctx: Arc<Ctx>) {
+    let mut repository = ctx.repository.clone();
+    if let Err(e) = remember_chat(&mut repository, message.chat.id.0, message.chat.title()).await {
+        log::error!("Failed to remember chat {}: '{e}'", message.chat.id);
+    }
+}
+
+/// Keeps track of chats which the bot is added to or removed from.
+pub async fn update_chat_membership(
+    update: ChatMemberUpdated,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<()> {
+    let is_member = update.new_chat_member.is_present();
+    log::info!(
+        "Bot is {} chat {}",
+        if is_member {
+            "added to"
+        } else {
+            "removed from"
+        },
+        update.chat.id
+    );
+
+    let mut repository = ctx.repository.clone();
+    repository
+        .upsert_chat(update.chat.id.0, update.chat.title(), is_member)
+        .await
+}
@@ -0,0 +1,232 @@
+use url::Url;
+
+// Query parameters which tell where a link was shared from rather than what it points to
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "yclid", "msclkid", "igshid", "mc_cid", "mc_eid", "_hsenc",
+    "_hsmi", "ref", "ref_src", "ref_url", "si", "feature", "share", "spm", "from", "cmpid",
+];
+const TRACKING_PARAM_PREFIXES: &[&str] = &["utm_", "at_"];
+
+// Subdomains which serve the same pages as the bare domain
+const ALIAS_SUBDOMAINS: &[&str] = &["www.", "m.", "mobile.", "amp."];
+
+/// Brings a link to the form under which the same page is always stored:
+/// https, no alias subdomains, tracking parameters, fragments and trailing slashes,
+/// sorted query parameters and a single form of YouTube video links.
+/// Returns `None` for anything which is not a web link.
+pub fn canonicalize(link: &str) -> Option<String> {
+    let link = link.trim();
+    // entities of the `Url` kind may come without a scheme
+    let url = match Url::parse(link) {
+        Ok(url) => url,
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            Url::parse(&format!("https://{link}")).ok()?
+        }
+        Err(_) => return None,
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    let mut host = url.host_str()?.trim_end_matches('.').to_lowercase();
+    while let Some(stripped) = ALIAS_SUBDOMAINS
+        .iter()
+        .find_map(|x| host.strip_prefix(x).map(str::to_string))
+    {
+        host = stripped;
+    }
+    if !host.contains('.') {
+        return None;
+    }
+
+    if let Some(video_id) = youtube_video_id(&host, &url) {
+        return Some(format!("https://youtube.com/watch?v={video_id}"));
+    }
+
+    let mut params = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    params.sort();
+
+    let path = url.path().trim_end_matches('/');
+    let mut canonical = Url::parse(&format!("https://{host}")).ok()?;
+    canonical.set_port(url.port()).ok()?;
+    canonical.set_path(path);
+    if !params.is_empty() {
+        canonical.query_pairs_mut().extend_pairs(params);
+    }
+
+    let canonical = canonical.to_string();
+    // the root path is always kept by the serializer
+    Some(match canonical.strip_suffix('/') {
+        Some(x) if path.is_empty() => x.to_string(),
+        _ => canonical,
+    })
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    let key = key.to_lowercase();
+    TRACKING_PARAMS.contains(&key.as_str())
+        || TRACKING_PARAM_PREFIXES.iter().any(|x| key.starts_with(x))
+}
+
+fn youtube_video_id(host: &str, url: &Url) -> Option<String> {
+    let mut segments = url.path_segments()?.filter(|x| !x.is_empty());
+    let id = match host {
+        "youtu.be" => segments.next()?.to_string(),
+        "youtube.com" | "music.youtube.com" | "youtube-nocookie.com" => match segments.next()? {
+            "watch" => url
+                .query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, value)| value.into_owned())?,
+            "shorts" | "embed" | "live" | "v" => segments.next()?.to_string(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let is_valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_');
+    is_valid.then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canonicalize;
+
+    #[test]
+    fn test_canonicalize() {
+        let video = Some("https://youtube.com/watch?v=dQw4w9WgXcQ");
+        for (link, expected) in [
+            // YouTube videos
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ", video),
+            ("https://youtube.com/watch?v=dQw4w9WgXcQ&t=42s", video),
+            (
+                "https://m.youtube.com/watch?feature=share&v=dQw4w9WgXcQ",
+                video,
+            ),
+            ("http://youtu.be/dQw4w9WgXcQ", video),
+            ("https://youtu.be/dQw4w9WgXcQ?si=AbCdEfGh", video),
+            ("youtu.be/dQw4w9WgXcQ", video),
+            ("https://www.youtube.com/shorts/dQw4w9WgXcQ", video),
+            (
+                "https://youtube.com/shorts/dQw4w9WgXcQ?feature=share",
+                video,
+            ),
+            ("https://www.youtube.com/embed/dQw4w9WgXcQ", video),
+            ("https://www.youtube.com/live/dQw4w9WgXcQ", video),
+            ("https://music.youtube.com/watch?v=dQw4w9WgXcQ", video),
+            ("https://WWW.YouTube.com/watch?v=dQw4w9WgXcQ", video),
+            // YouTube pages which are not videos
+            (
+                "https://www.youtube.com/@channel/videos/",
+                Some("https://youtube.com/@channel/videos"),
+            ),
+            (
+                "https://www.youtube.com/watch?list=PL123",
+                Some("https://youtube.com/watch?list=PL123"),
+            ),
+            // tracking parameters
+            (
+                "https://news.example.com/article?utm_source=tg&utm_medium=social",
+                Some("https://news.example.com/article"),
+            ),
+            (
+                "https://example.com/a?id=5&fbclid=IwAR0&gclid=x",
+                Some("https://example.com/a?id=5"),
+            ),
+            (
+                "https://example.com/a?UTM_Campaign=x&page=2",
+                Some("https://example.com/a?page=2"),
+            ),
+            (
+                "https://instagram.com/p/Cx1/?igshid=abc",
+                Some("https://instagram.com/p/Cx1"),
+            ),
+            // query parameters are sorted
+            (
+                "https://example.com/search?q=beer&page=2",
+                Some("https://example.com/search?page=2&q=beer"),
+            ),
+            // subdomains and schemes
+            (
+                "http://www.example.com/news",
+                Some("https://example.com/news"),
+            ),
+            (
+                "https://m.example.com/news",
+                Some("https://example.com/news"),
+            ),
+            (
+                "https://mobile.twitter.com/user/status/1",
+                Some("https://twitter.com/user/status/1"),
+            ),
+            (
+                "https://en.m.wikipedia.org/wiki/Beer",
+                Some("https://en.m.wikipedia.org/wiki/Beer"),
+            ),
+            (
+                "https://blog.example.com/post",
+                Some("https://blog.example.com/post"),
+            ),
+            // trailing slashes, fragments and ports
+            ("https://example.com/", Some("https://example.com")),
+            ("https://example.com", Some("https://example.com")),
+            (
+                "https://example.com/news/",
+                Some("https://example.com/news"),
+            ),
+            (
+                "https://example.com/news///",
+                Some("https://example.com/news"),
+            ),
+            (
+                "https://example.com/news#comments",
+                Some("https://example.com/news"),
+            ),
+            (
+                "https://example.com:443/news",
+                Some("https://example.com/news"),
+            ),
+            (
+                "http://example.com:8080/news",
+                Some("https://example.com:8080/news"),
+            ),
+            ("example.com/news/", Some("https://example.com/news")),
+            (
+                "  https://example.com/news  ",
+                Some("https://example.com/news"),
+            ),
+            // the case of paths matters
+            ("https://example.com/News", Some("https://example.com/News")),
+            // not web links
+            ("mailto:krusty@example.com", None),
+            ("ftp://example.com/file", None),
+            ("tg://resolve?domain=krusty", None),
+            ("localhost", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                canonicalize(link).as_deref(),
+                expected,
+                "canonical form of '{link}' is wrong"
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_form_is_stable() {
+        for link in [
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://m.example.com/a/?utm_source=x&b=2&a=1#top",
+            "https://example.com/",
+        ] {
+            let canonical = canonicalize(link).unwrap();
+            assert_eq!(canonicalize(&canonical), Some(canonical));
+        }
+    }
+}
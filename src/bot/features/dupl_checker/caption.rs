@@ -0,0 +1,117 @@
+use chrono::{prelude::*, Duration};
+
+use crate::database::types::OriginalPost;
+
+pub const DEFAULT_CAPTION_TEMPLATE: &str = "{user} already posted this {time_ago}: {link}";
+
+/// Fills `{user}`, `{time_ago}`, `{link}` and `{count}` in the template with details of the original post.
+/// Placeholders are filled in one pass, so the ones in names of posters are left as they are.
+pub fn render_caption(template: &str, original: &OriginalPost, now: DateTime<Utc>) -> String {
+    let value = |placeholder: &str| match placeholder {
+        "{user}" => Some(
+            original
+                .poster_name
+                .clone()
+                .unwrap_or_else(|| "Someone".to_string()),
+        ),
+        "{time_ago}" => Some(time_ago(now - original.posted_at)),
+        "{link}" => Some(original.message_url.clone()),
+        "{count}" => Some(original.repost_count.to_string()),
+        _ => None,
+    };
+
+    let mut caption = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        caption.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest.find('}').map_or(rest, |end| &rest[..=end]);
+        match value(placeholder) {
+            Some(value) => {
+                caption.push_str(&value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                caption.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    caption.push_str(rest);
+    caption
+}
+
+fn time_ago(elapsed: Duration) -> String {
+    let (amount, unit) = if elapsed < Duration::minutes(1) {
+        return "just now".to_string();
+    } else if elapsed < Duration::hours(1) {
+        (elapsed.num_minutes(), "minute")
+    } else if elapsed < Duration::days(1) {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed < Duration::days(30) {
+        (elapsed.num_days(), "day")
+    } else if elapsed < Duration::days(365) {
+        (elapsed.num_days() / 30, "month")
+    } else {
+        (elapsed.num_days() / 365, "year")
+    };
+
+    if amount == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{amount} {unit}s ago")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{prelude::*, Duration};
+
+    use crate::database::types::OriginalPost;
+
+    use super::{render_caption, time_ago, DEFAULT_CAPTION_TEMPLATE};
+
+    #[test]
+    fn test_time_ago() {
+        assert_eq!(time_ago(Duration::seconds(59)), "just now");
+        assert_eq!(time_ago(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(time_ago(Duration::minutes(59)), "59 minutes ago");
+        assert_eq!(time_ago(Duration::hours(5)), "5 hours ago");
+        assert_eq!(time_ago(Duration::days(1)), "1 day ago");
+        assert_eq!(time_ago(Duration::days(64)), "2 months ago");
+        assert_eq!(time_ago(Duration::days(800)), "2 years ago");
+    }
+
+    #[test]
+    fn test_render_caption() {
+        let now = Utc::now();
+        let mut original = OriginalPost {
+            message_url: "https://t.me/c/1/2".to_string(),
+            poster_id: Some(42),
+            poster_name: Some("Homer Simpson".to_string()),
+            posted_at: now - Duration::hours(3),
+            repost_count: 4,
+        };
+
+        assert_eq!(
+            render_caption(DEFAULT_CAPTION_TEMPLATE, &original, now),
+            "Homer Simpson already posted this 3 hours ago: https://t.me/c/1/2"
+        );
+        assert_eq!(
+            render_caption("{link} (reposted {count} times)", &original, now),
+            "https://t.me/c/1/2 (reposted 4 times)"
+        );
+
+        original.poster_name = None;
+        assert_eq!(
+            render_caption("{user} was first", &original, now),
+            "Someone was first"
+        );
+
+        original.poster_name = Some("{link} {count}".to_string());
+        assert_eq!(
+            render_caption("{{user}} posted {link}, {unknown}", &original, now),
+            "{{link} {count}} posted https://t.me/c/1/2, {unknown}"
+        );
+    }
+}
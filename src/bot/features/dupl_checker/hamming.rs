@@ -0,0 +1,41 @@
+/// Number of differing bits, the lesser the more alike hashed posts are.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// The item with the hash nearest to the given one, if it's within `max_distance`.
+pub fn closest<T>(
+    known: &[T],
+    hash: u64,
+    max_distance: u32,
+    hash_of: impl Fn(&T) -> u64,
+) -> Option<&T> {
+    known
+        .iter()
+        .map(|x| (hamming_distance(hash_of(x), hash), x))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, x)| x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, hamming_distance};
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(0b1011, 0b0010), 2);
+        assert_eq!(hamming_distance(u64::MAX, 0), 64);
+    }
+
+    #[test]
+    fn test_closest() {
+        let known = [(0b1111, "first"), (0b0111, "second"), (u64::MAX, "third")];
+        let nearest = |hash| closest(&known, hash, 2, |x| x.0).map(|x| x.1);
+
+        assert_eq!(nearest(0b0011), Some("second"));
+        assert_eq!(nearest(u64::MAX - 1), Some("third"));
+        assert_eq!(nearest(0b1111_0000_0000), None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+use teloxide::types::{Me, MessageEntityKind};
+use teloxide::{prelude::*, Bot};
+
+use crate::bot::{ctx::Ctx, settings::effective_settings, utils::topic_id};
+use crate::database::types::PostedLink;
+
+use super::canonical_url::canonicalize;
+use super::{duplicate_window_start, poster, reply_with_original};
+
+/// Responds to the message if any of its links has been posted to the chat before,
+/// links which are new are remembered. Returns whether the response has been sent.
+pub async fn send_media_if_link_posted_before(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<bool> {
+    let links = canonical_links(message);
+    if links.is_empty() {
+        return Ok(false);
+    }
+    let chat_id = message.chat.id;
+
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        chat_id,
+        topic_id(message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.duplicate_detection_enabled {
+        return Ok(false);
+    }
+
+    let since = duplicate_window_start(&mut repository, &ctx, chat_id).await?;
+    let message_url = message
+        .url()
+        .expect("Message link should be obtainable if the bot is used in supergroup");
+    let (poster_id, poster_name) = poster(message);
+    let mut original = None;
+    for link in links {
+        let link = PostedLink {
+            chat_id: chat_id.0,
+            url: link,
+            message_url: message_url.to_string(),
+            poster_id,
+            poster_name: poster_name.clone(),
+        };
+        if let Some(posted) = repository
+            .insert_posted_link_or_existing(&link, since)
+            .await?
+        {
+            original.get_or_insert(posted);
+        }
+    }
+
+    match original {
+        Some(original) => {
+            reply_with_original(
+                message,
+                bot,
+                me,
+                &ctx,
+                &mut repository,
+                &settings,
+                &original,
+            )
+            .await
+        }
+        None => Ok(false),
+    }
+}
+
+// Links of the text or of the caption, without duplicates and in order of appearance.
+fn canonical_links(message: &Message) -> Vec<String> {
+    let entities = message
+        .parse_entities()
+        .or_else(|| message.parse_caption_entities())
+        .unwrap_or_default();
+
+    let mut links = Vec::new();
+    for entity in entities {
+        let link = match entity.kind() {
+            MessageEntityKind::Url => canonicalize(entity.text()),
+            MessageEntityKind::TextLink { url } => canonicalize(url.as_str()),
+            _ => None,
+        };
+        if let Some(link) = link.filter(|x| !links.contains(x)) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::bot::test_utils::supergroup_message;
+
+    use super::canonical_links;
+
+    #[test]
+    fn test_canonical_links() {
+        let text =
+            "look youtu.be/dQw4w9WgXcQ and this and https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+        let message = supergroup_message(
+            text,
+            json!({"entities": [
+                {"type": "url", "offset": 5, "length": 20},
+                {"type": "text_link", "offset": 30, "length": 4, "url": "https://example.com/news/?utm_source=tg"},
+                {"type": "bold", "offset": 0, "length": 4},
+                {"type": "url", "offset": 39, "length": 43},
+            ]}),
+        );
+
+        assert_eq!(
+            canonical_links(&message),
+            [
+                "https://youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://example.com/news",
+            ]
+        );
+    }
+}
@@ -0,0 +1,310 @@
+mod canonical_url;
+mod caption;
+mod hamming;
+mod links;
+mod moderation;
+mod perceptual_hash;
+mod photos;
+mod retention;
+mod sender_forwards;
+mod simhash;
+mod texts;
+
+use anyhow::anyhow;
+use chrono::{prelude::*, Duration};
+use std::future::Future;
+use std::sync::Arc;
+use teloxide::types::Me;
+use teloxide::{prelude::*, Bot};
+
+use crate::{
+    bot::{
+        cache::{
+            chat_duplicate_window_by_id, chat_moderation_settings_by_id, feature_reply_options,
+            media_info_by_feature_type,
+        },
+        ctx::Ctx,
+        features::auto_delete::delete_reply_later,
+        settings::{effective_settings, EffectiveSettings},
+        utils::{
+            choose_random_media_info, is_time_passed, send_media, topic_id, Caption, Delivery,
+        },
+    },
+    database::{
+        repository::AsyncRepository,
+        types::{
+            DuplicatePolicy, ForwardedMessage, MediaFeatureType, NewRepostEvent, OriginalPost,
+        },
+    },
+    formatting::append_escaped,
+};
+
+use self::caption::render_caption;
+use self::moderation::moderate_duplicate;
+
+pub use self::caption::DEFAULT_CAPTION_TEMPLATE;
+pub use self::links::send_media_if_link_posted_before;
+pub use self::photos::send_media_if_photo_posted_before;
+pub use self::retention::prune_duplicate_candidates_periodically;
+pub use self::sender_forwards::send_media_if_sender_forward_posted_before;
+pub use self::texts::send_media_if_text_posted_before;
+
+/// Thresholds of content-based duplicate detection.
+#[derive(Clone, Debug)]
+pub struct DuplicateConfig {
+    /// Max number of differing bits of perceptual hashes of alike photos.
+    pub max_photo_hash_distance: u32,
+    /// Max number of differing bits of SimHashes of alike texts.
+    pub max_text_hash_distance: u32,
+    /// Texts with fewer letters and digits are never checked.
+    pub min_text_length: usize,
+    /// Only duplicates of messages newer than this count in chats without a window of their own.
+    pub default_window_days: Option<i32>,
+    /// Caption of the response to a duplicate, see `render_caption` for placeholders.
+    pub caption_template: String,
+}
+
+pub async fn send_media_if_forwarded_before(
+    message: Message,
+    bot: Bot,
+    me: Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<()> {
+    let chat_id = message.chat.id;
+    let message_url = message
+        .url()
+        .expect("Message link should be obtainable if the bot is used in supergroup");
+
+    let forwarded_message_id = message
+        .forward_from_message_id()
+        .ok_or_else(|| anyhow!("Non-forwarded message is handled in 'forward-only' handler"))?;
+    let forwarded_chat_id = message
+        .forward_from_chat()
+        .ok_or_else(|| anyhow!("Non-forwarded message is handled in 'forward-only' handler"))?
+        .id
+        .0;
+
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        chat_id,
+        topic_id(&message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.duplicate_detection_enabled {
+        return Ok(());
+    }
+
+    let since = duplicate_window_start(&mut repository, &ctx, chat_id).await?;
+    let (poster_id, poster_name) = poster(&message);
+    let original = repository
+        .insert_forward_message_or_existing(
+            &ForwardedMessage {
+                chat_id: chat_id.0,
+                forwarded_message_id,
+                message_url: message_url.to_string(),
+                forwarded_chat_id,
+                poster_id,
+                poster_name,
+            },
+            since,
+        )
+        .await?;
+
+    if let Some(original) = original {
+        reply_with_original(
+            &message,
+            bot,
+            &me,
+            &ctx,
+            &mut repository,
+            &settings,
+            &original,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Id and name of the user or of the chat on behalf of which the message has been sent.
+pub fn poster(message: &Message) -> (Option<i64>, Option<String>) {
+    match (message.sender_chat(), message.from()) {
+        (Some(chat), _) => (Some(chat.id.0), chat.title().map(str::to_string)),
+        (None, Some(user)) => (Some(user.id.0 as i64), Some(user.full_name())),
+        (None, None) => (None, None),
+    }
+}
+
+/// Messages posted before the returned time are too old to have duplicates.
+async fn duplicate_window_start(
+    repository: &mut AsyncRepository,
+    ctx: &Ctx,
+    chat_id: ChatId,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let window_days = chat_duplicate_window_by_id(repository, chat_id.0).await?;
+    Ok(window_start(
+        window_days,
+        ctx.duplicates.default_window_days,
+        Utc::now(),
+    ))
+}
+
+fn window_start(
+    chat_window_days: Option<i32>,
+    default_window_days: Option<i32>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    chat_window_days
+        .or(default_window_days)
+        .map(|x| now - Duration::days(x.into()))
+}
+
+/// Responds to the post alike the one at `alike_url` after `register_repost` has counted it.
+/// The original may have been pruned since, then the post is a new original
+/// and is given to `remember` with its link and poster. Returns whether the response has been sent.
+#[allow(clippy::too_many_arguments)]
+async fn reply_with_alike_or_remember<R, RF, S, SF>(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: &Ctx,
+    settings: &EffectiveSettings,
+    alike_url: Option<&str>,
+    register_repost: R,
+    remember: S,
+) -> anyhow::Result<bool>
+where
+    R: FnOnce(AsyncRepository, String) -> RF,
+    RF: Future<Output = anyhow::Result<Option<OriginalPost>>>,
+    S: FnOnce(AsyncRepository, String, (Option<i64>, Option<String>)) -> SF,
+    SF: Future<Output = anyhow::Result<()>>,
+{
+    let mut repository = ctx.repository.clone();
+    let original = match alike_url {
+        Some(url) => register_repost(repository.clone(), url.to_string()).await?,
+        None => None,
+    };
+    match original {
+        Some(original) => {
+            reply_with_original(message, bot, me, ctx, &mut repository, settings, &original).await
+        }
+        None => {
+            let message_url = message
+                .url()
+                .expect("Message link should be obtainable if the bot is used in supergroup");
+            remember(repository, message_url.to_string(), poster(message)).await?;
+            Ok(false)
+        }
+    }
+}
+
+/// Records the repost and moderates it or responds to it with details of the original message
+/// unless the chat is on cooldown. Returns whether the response has been sent.
+async fn reply_with_original(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: &Ctx,
+    repository: &mut AsyncRepository,
+    settings: &EffectiveSettings,
+    original: &OriginalPost,
+) -> anyhow::Result<bool> {
+    let (reposter_id, reposter_name) = poster(message);
+    repository
+        .insert_repost_event(&NewRepostEvent {
+            chat_id: message.chat.id.0,
+            reposter_id,
+            reposter_name,
+            message_url: message
+                .url()
+                .expect("Message link should be obtainable if the bot is used in supergroup")
+                .to_string(),
+            original_url: original.message_url.clone(),
+            original_poster_id: original.poster_id,
+        })
+        .await?;
+
+    let caption = render_caption(&ctx.duplicates.caption_template, original, Utc::now());
+    let moderation = chat_moderation_settings_by_id(repository, message.chat.id.0)
+        .await?
+        .unwrap_or_default();
+    if moderation.policy != DuplicatePolicy::Reply
+        && moderate_duplicate(message, &bot, me, repository, &moderation, &caption).await?
+    {
+        return Ok(true);
+    }
+
+    let media_infos = media_info_by_feature_type(
+        repository,
+        MediaFeatureType::DuplicatedForwardedMessageDetection,
+    )
+    .await?;
+
+    let Some(media) = choose_random_media_info(&media_infos) else {
+        return Ok(false);
+    };
+
+    {
+        log::debug!("Locking duplicate forward chat mutex");
+        let mut chat_times = ctx.duplicate_forward_timestamps.lock().await;
+        if let Some(time) = chat_times.get(&settings.cooldown_key) {
+            if !is_time_passed(time, &settings.cooldown) {
+                return Ok(false);
+            }
+        }
+        chat_times.insert(settings.cooldown_key, Utc::now());
+    }
+
+    let options = feature_reply_options(
+        repository,
+        MediaFeatureType::DuplicatedForwardedMessageDetection,
+    )
+    .await?;
+    let message_ids = send_media(
+        media,
+        repository,
+        &ctx.media_stores,
+        bot.clone(),
+        message.chat.id,
+        Delivery::response_to(message, options),
+        Some(Caption {
+            text: append_escaped(media.caption.as_deref(), &caption, &media.parse_mode),
+            parse_mode: media.parse_mode.clone(),
+        }),
+    )
+    .await?;
+    delete_reply_later(
+        bot,
+        repository,
+        message.chat.id,
+        &message_ids,
+        &media.name,
+        None,
+    )
+    .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{prelude::*, Duration};
+
+    use super::window_start;
+
+    #[test]
+    fn test_window_start() {
+        let now = Utc::now();
+        assert_eq!(
+            window_start(Some(7), Some(30), now),
+            Some(now - Duration::days(7))
+        );
+        assert_eq!(
+            window_start(None, Some(30), now),
+            Some(now - Duration::days(30))
+        );
+        assert_eq!(window_start(None, None, now), None);
+    }
+}
@@ -0,0 +1,274 @@
+use cached::proc_macro::cached;
+use cached::TimedSizedCache;
+use chrono::{prelude::*, Duration};
+use teloxide::types::{ChatPermissions, Me, UserId};
+use teloxide::{prelude::*, Bot};
+
+use crate::bot::utils::{send_text, Delivery};
+use crate::database::{
+    repository::AsyncRepository,
+    types::{DuplicatePolicy, ModerationSettings, ReplyOptions},
+};
+
+use super::poster;
+
+const DEFAULT_MUTE_AFTER_DUPLICATES: i32 = 3;
+const DEFAULT_MUTE_WINDOW_HOURS: i32 = 24;
+const DEFAULT_MUTE_MINUTES: i32 = 60;
+
+/// What is done to a duplicate.
+#[derive(Debug, PartialEq, Eq)]
+enum Sanction {
+    /// A media in response, as in chats without moderation.
+    Reply,
+    /// The duplicate is deleted and a notice is posted instead.
+    Delete,
+    /// Just the notice, for senders who can't be muted.
+    Notice,
+    /// The reposter is warned about the mute.
+    Warn { duplicates: i64, limit: i64 },
+    /// The reposter can't send messages for a while.
+    Mute { minutes: i64 },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct BotRights {
+    can_delete_messages: bool,
+    can_restrict_members: bool,
+}
+
+/// Deals with the duplicate according to the policy of the chat.
+/// Returns false if it should be responded to with a media instead,
+/// e.g. when the bot lacks admin rights to moderate it.
+pub async fn moderate_duplicate(
+    message: &Message,
+    bot: &Bot,
+    me: &Me,
+    repository: &mut AsyncRepository,
+    settings: &ModerationSettings,
+    notice: &str,
+) -> anyhow::Result<bool> {
+    let chat_id = message.chat.id;
+    let rights = match bot_rights(bot, me.id, chat_id).await {
+        Ok(rights) => rights,
+        Err(e) => {
+            log::warn!("Failed to get admin rights in chat {chat_id}: '{e}'");
+            return Ok(false);
+        }
+    };
+    // anonymous admins and channels can't be restricted
+    let user_id = message
+        .from()
+        .filter(|_| message.sender_chat().is_none())
+        .map(|x| x.id);
+
+    let duplicates = match user_id {
+        Some(user_id) if settings.policy == DuplicatePolicy::Escalate => {
+            let window = settings
+                .mute_window_hours
+                .unwrap_or(DEFAULT_MUTE_WINDOW_HOURS);
+            repository
+                .user_repost_counts(
+                    chat_id.0,
+                    user_id.0 as i64,
+                    Utc::now() - Duration::hours(window.into()),
+                )
+                .await?
+                .0
+        }
+        _ => 0,
+    };
+
+    let mut sanction = sanction(settings, duplicates, rights, user_id.is_some());
+    if sanction == Sanction::Reply {
+        log::warn!("Lacking admin rights to moderate duplicates in chat {chat_id}");
+        return Ok(false);
+    }
+
+    match (&sanction, user_id) {
+        (Sanction::Delete, _) => {
+            if let Err(e) = bot.delete_message(chat_id, message.id).await {
+                log::warn!("Failed to delete duplicate in chat {chat_id}: '{e}'");
+                return Ok(false);
+            }
+        }
+        (Sanction::Mute { minutes }, Some(user_id)) => {
+            if let Err(e) = mute(bot, chat_id, user_id, *minutes).await {
+                // e.g. admins can't be restricted
+                log::warn!("Failed to mute reposter in chat {chat_id}: '{e}'");
+                sanction = Sanction::Warn {
+                    duplicates,
+                    limit: duplicates,
+                };
+            }
+        }
+        _ => {}
+    }
+
+    let delivery = Delivery::response_to(message, ReplyOptions::default());
+    let delivery = match sanction {
+        Sanction::Delete => delivery.without_reply(),
+        _ => delivery,
+    };
+    let (_, reposter_name) = poster(message);
+    send_text(
+        bot,
+        chat_id,
+        notice_text(&sanction, reposter_name.as_deref(), notice),
+        delivery,
+    )
+    .await?;
+
+    Ok(true)
+}
+
+// Rights rarely change, but are needed for every duplicate.
+#[cached(
+    type = "TimedSizedCache<ChatId, BotRights>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 300) }",
+    result = true,
+    convert = "{ chat_id }"
+)]
+async fn bot_rights(bot: &Bot, bot_id: UserId, chat_id: ChatId) -> anyhow::Result<BotRights> {
+    let member = bot.get_chat_member(chat_id, bot_id).await?;
+    Ok(BotRights {
+        can_delete_messages: member.can_delete_messages(),
+        can_restrict_members: member.can_restrict_members(),
+    })
+}
+
+async fn mute(bot: &Bot, chat_id: ChatId, user_id: UserId, minutes: i64) -> anyhow::Result<()> {
+    bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty())
+        .until_date(Utc::now() + Duration::minutes(minutes))
+        .await?;
+    Ok(())
+}
+
+// Policies degrade to milder sanctions which the bot has rights for.
+fn sanction(
+    settings: &ModerationSettings,
+    duplicates: i64,
+    rights: BotRights,
+    is_user: bool,
+) -> Sanction {
+    match settings.policy {
+        DuplicatePolicy::Reply => Sanction::Reply,
+        DuplicatePolicy::Delete if rights.can_delete_messages => Sanction::Delete,
+        DuplicatePolicy::Delete => Sanction::Reply,
+        DuplicatePolicy::Escalate if !is_user => Sanction::Notice,
+        DuplicatePolicy::Escalate => {
+            let limit = settings
+                .mute_after_duplicates
+                .unwrap_or(DEFAULT_MUTE_AFTER_DUPLICATES)
+                .into();
+            if duplicates >= limit && rights.can_restrict_members {
+                Sanction::Mute {
+                    minutes: settings.mute_minutes.unwrap_or(DEFAULT_MUTE_MINUTES).into(),
+                }
+            } else {
+                Sanction::Warn { duplicates, limit }
+            }
+        }
+    }
+}
+
+fn notice_text(sanction: &Sanction, reposter_name: Option<&str>, notice: &str) -> String {
+    let name = reposter_name.unwrap_or("Someone");
+    match sanction {
+        Sanction::Reply | Sanction::Notice => notice.to_string(),
+        Sanction::Delete => format!("Deleted a duplicate from {name}. {notice}"),
+        Sanction::Warn { duplicates, limit } if duplicates < limit => format!(
+            "{notice}\nWarning {duplicates}/{limit}, {name} gets muted for reposting {limit} times."
+        ),
+        Sanction::Warn { .. } => format!("{notice}\n{name}, stop reposting."),
+        Sanction::Mute { minutes } => {
+            format!("{notice}\n{name} is muted for {minutes} minutes for reposting.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::types::{DuplicatePolicy, ModerationSettings};
+
+    use super::{notice_text, sanction, BotRights, Sanction};
+
+    const ADMIN: BotRights = BotRights {
+        can_delete_messages: true,
+        can_restrict_members: true,
+    };
+    const MEMBER: BotRights = BotRights {
+        can_delete_messages: false,
+        can_restrict_members: false,
+    };
+
+    fn settings(policy: DuplicatePolicy) -> ModerationSettings {
+        ModerationSettings {
+            policy,
+            mute_after_duplicates: Some(3),
+            mute_window_hours: None,
+            mute_minutes: Some(10),
+        }
+    }
+
+    #[test]
+    fn test_delete_policy() {
+        let delete = settings(DuplicatePolicy::Delete);
+        assert_eq!(sanction(&delete, 0, ADMIN, true), Sanction::Delete);
+        assert_eq!(sanction(&delete, 0, MEMBER, true), Sanction::Reply);
+    }
+
+    #[test]
+    fn test_escalate_policy() {
+        let escalate = settings(DuplicatePolicy::Escalate);
+        assert_eq!(
+            sanction(&escalate, 1, ADMIN, true),
+            Sanction::Warn {
+                duplicates: 1,
+                limit: 3
+            }
+        );
+        assert_eq!(
+            sanction(&escalate, 3, ADMIN, true),
+            Sanction::Mute { minutes: 10 }
+        );
+        assert_eq!(
+            sanction(&escalate, 3, MEMBER, true),
+            Sanction::Warn {
+                duplicates: 3,
+                limit: 3
+            }
+        );
+        assert_eq!(sanction(&escalate, 3, ADMIN, false), Sanction::Notice);
+    }
+
+    #[test]
+    fn test_reply_policy() {
+        let reply = settings(DuplicatePolicy::Reply);
+        assert_eq!(sanction(&reply, 5, ADMIN, true), Sanction::Reply);
+    }
+
+    #[test]
+    fn test_notice_text() {
+        let warn = Sanction::Warn {
+            duplicates: 1,
+            limit: 3,
+        };
+        assert_eq!(
+            notice_text(&warn, Some("Bart"), "Seen it"),
+            "Seen it\nWarning 1/3, Bart gets muted for reposting 3 times."
+        );
+        assert_eq!(
+            notice_text(&Sanction::Mute { minutes: 10 }, None, "Seen it"),
+            "Seen it\nSomeone is muted for 10 minutes for reposting."
+        );
+        assert_eq!(
+            notice_text(&Sanction::Delete, Some("Bart"), "Seen it"),
+            "Deleted a duplicate from Bart. Seen it"
+        );
+        assert_eq!(
+            notice_text(&Sanction::Notice, Some("Channel"), "Seen it"),
+            "Seen it"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use image::imageops::{self, FilterType};
+
+const WIDTH: u32 = 9;
+const HEIGHT: u32 = 8;
+
+/// Computes the 64-bit difference hash of the picture: every bit tells whether a pixel
+/// of its 9x8 grayscale copy is brighter than the right neighbour.
+/// Resizing and re-compression barely change it, unlike a cryptographic hash.
+pub fn difference_hash(data: &[u8]) -> anyhow::Result<u64> {
+    let image = image::load_from_memory(data)?.into_luma8();
+    let image = imageops::resize(&image, WIDTH, HEIGHT, FilterType::Triangle);
+
+    let mut hash = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH - 1 {
+            let brighter = image.get_pixel(x, y)[0] > image.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | u64::from(brighter);
+        }
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{ImageBuffer, Rgb};
+
+    use crate::bot::features::dupl_checker::hamming::hamming_distance;
+
+    use super::difference_hash;
+
+    fn jpeg(width: u32, height: u32, quality: u8, pixel: impl Fn(f32, f32) -> u8) -> Vec<u8> {
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            let v = pixel(x as f32 / width as f32, y as f32 / height as f32);
+            Rgb([v, v, v])
+        });
+
+        let mut data = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, quality)
+            .encode_image(&image)
+            .unwrap();
+        data
+    }
+
+    // a meme-like picture: bright blobs on a dark background
+    fn meme(x: f32, y: f32) -> u8 {
+        let v = (x * 7.0).sin() * (y * 5.0).cos();
+        (128.0 + 127.0 * v) as u8
+    }
+
+    #[test]
+    fn test_resized_and_recompressed_picture() {
+        let original = difference_hash(&jpeg(640, 480, 90, meme)).unwrap();
+        let copy = difference_hash(&jpeg(320, 240, 40, meme)).unwrap();
+
+        assert!(hamming_distance(original, copy) <= 4);
+    }
+
+    #[test]
+    fn test_different_pictures() {
+        let original = difference_hash(&jpeg(640, 480, 90, meme)).unwrap();
+        let other = difference_hash(&jpeg(640, 480, 90, |x, y| meme(y, x))).unwrap();
+
+        assert!(hamming_distance(original, other) > 16);
+    }
+
+    #[test]
+    fn test_not_a_picture() {
+        assert!(difference_hash(b"definitely not a picture").is_err());
+    }
+}
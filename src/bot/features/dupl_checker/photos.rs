@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use teloxide::net::Download;
+use teloxide::types::Me;
+use teloxide::{prelude::*, Bot};
+
+use crate::bot::{ctx::Ctx, settings::effective_settings, utils::topic_id};
+use crate::database::types::PhotoHash;
+
+use super::hamming::closest;
+use super::perceptual_hash::difference_hash;
+use super::{duplicate_window_start, reply_with_alike_or_remember};
+
+/// Responds to the photo if an alike one has been posted to the chat before,
+/// otherwise remembers it. Returns whether the response has been sent.
+pub async fn send_media_if_photo_posted_before(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<bool> {
+    let Some(photo) = message
+        .photo()
+        .and_then(|sizes| sizes.iter().max_by_key(|x| x.width * x.height))
+    else {
+        return Ok(false);
+    };
+    let chat_id = message.chat.id;
+
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        chat_id,
+        topic_id(message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.duplicate_detection_enabled {
+        return Ok(false);
+    }
+
+    let file = bot.get_file(&photo.file.id).await?;
+    let mut data = Vec::with_capacity(file.size as usize);
+    bot.download_file(&file.path, &mut data).await?;
+    let hash = difference_hash(&data)?;
+
+    let since = duplicate_window_start(&mut repository, &ctx, chat_id).await?;
+    let known = repository.photo_hashes_by_chat_id(chat_id.0, since).await?;
+    let alike = closest(&known, hash, ctx.duplicates.max_photo_hash_distance, |x| {
+        x.hash as u64
+    });
+    reply_with_alike_or_remember(
+        message,
+        bot,
+        me,
+        &ctx,
+        &settings,
+        alike.map(|x| x.message_url.as_str()),
+        |mut repository, url| async move {
+            repository.register_photo_repost(chat_id.0, &url).await
+        },
+        |mut repository, message_url, (poster_id, poster_name)| async move {
+            repository
+                .insert_photo_hash(&PhotoHash {
+                    chat_id: chat_id.0,
+                    hash: hash as i64,
+                    message_url,
+                    poster_id,
+                    poster_name,
+                })
+                .await
+        },
+    )
+    .await
+}
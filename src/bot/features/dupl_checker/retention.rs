@@ -0,0 +1,22 @@
+use crate::database::repository::AsyncRepository;
+
+const PRUNING_PERIOD: tokio::time::Duration = tokio::time::Duration::from_secs(3600);
+
+/// Deletes messages which are too old to have duplicates, once in a while.
+pub async fn prune_duplicate_candidates_periodically(
+    mut repository: AsyncRepository,
+    default_window_days: Option<i32>,
+) {
+    let mut interval = tokio::time::interval(PRUNING_PERIOD);
+    loop {
+        interval.tick().await;
+        match repository
+            .prune_duplicate_candidates(default_window_days)
+            .await
+        {
+            Ok(0) => {}
+            Ok(count) => log::info!("Pruned {count} messages kept for duplicate detection"),
+            Err(e) => log::error!("Failed to prune messages kept for duplicate detection: '{e}'"),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::prelude::*;
+use std::sync::Arc;
+use teloxide::types::{ForwardedFrom, Me};
+use teloxide::{prelude::*, Bot};
+
+use crate::bot::{ctx::Ctx, settings::effective_settings, utils::topic_id};
+use crate::database::types::ForwardFingerprint;
+
+use super::simhash::fnv1a;
+use super::{duplicate_window_start, poster, reply_with_original};
+
+/// Responds to the forward of a user message, of a hidden sender message or of an anonymous
+/// admin message if it has been forwarded to the chat before, otherwise remembers it.
+/// Channel posts are handled by `send_media_if_forwarded_before`.
+/// Returns whether the response has been sent.
+pub async fn send_media_if_sender_forward_posted_before(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<bool> {
+    let Some((origin, forward_date, content_hash)) = fingerprint(message) else {
+        return Ok(false);
+    };
+    let chat_id = message.chat.id;
+
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        chat_id,
+        topic_id(message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.duplicate_detection_enabled {
+        return Ok(false);
+    }
+
+    let message_url = message
+        .url()
+        .expect("Message link should be obtainable if the bot is used in supergroup");
+    let since = duplicate_window_start(&mut repository, &ctx, chat_id).await?;
+    let (poster_id, poster_name) = poster(message);
+    let original = repository
+        .insert_forward_fingerprint_or_existing(
+            &ForwardFingerprint {
+                chat_id: chat_id.0,
+                origin,
+                forward_date,
+                content_hash: content_hash as i64,
+                message_url: message_url.to_string(),
+                poster_id,
+                poster_name,
+            },
+            since,
+        )
+        .await?;
+
+    match original {
+        Some(original) => {
+            reply_with_original(
+                message,
+                bot,
+                me,
+                &ctx,
+                &mut repository,
+                &settings,
+                &original,
+            )
+            .await
+        }
+        None => Ok(false),
+    }
+}
+
+// Who sent the original message, when and what it contains.
+fn fingerprint(message: &Message) -> Option<(String, DateTime<Utc>, u64)> {
+    let forward = message.forward()?;
+    let origin = match &forward.from {
+        ForwardedFrom::User(user) => format!("user:{}", user.id),
+        ForwardedFrom::SenderName(name) => format!("sender:{name}"),
+        // posts are identified by their ids
+        ForwardedFrom::Chat(_) if forward.message_id.is_some() => return None,
+        ForwardedFrom::Chat(chat) => format!("chat:{}", chat.id),
+    };
+
+    Some((origin, forward.date, content_hash(message)))
+}
+
+// Files are identified by their unique ids, which are the same for every copy of a file.
+fn content_hash(message: &Message) -> u64 {
+    let mut content = message
+        .text()
+        .or_else(|| message.caption())
+        .unwrap_or_default()
+        .to_string();
+
+    let file_ids = [
+        message
+            .photo()
+            .and_then(|x| x.iter().max_by_key(|x| x.width * x.height))
+            .map(|x| &x.file.unique_id),
+        message.video().map(|x| &x.file.unique_id),
+        message.animation().map(|x| &x.file.unique_id),
+        message.document().map(|x| &x.file.unique_id),
+        message.audio().map(|x| &x.file.unique_id),
+        message.voice().map(|x| &x.file.unique_id),
+        message.video_note().map(|x| &x.file.unique_id),
+        message.sticker().map(|x| &x.file.unique_id),
+    ];
+    for file_id in file_ids.into_iter().flatten() {
+        content.push('\n');
+        content.push_str(file_id);
+    }
+
+    fnv1a(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use serde_json::{json, Value};
+    use teloxide::types::Message;
+
+    use crate::bot::test_utils::supergroup_message;
+
+    use super::fingerprint;
+
+    fn forward(mut origin: Value, text: &str) -> Message {
+        origin["forward_date"] = json!(1694000000);
+        supergroup_message(text, origin)
+    }
+
+    fn user() -> Value {
+        json!({"forward_from": {"id": 7, "is_bot": false, "first_name": "Bart"}})
+    }
+
+    #[test]
+    fn test_user_forward() {
+        let (origin, date, _) = fingerprint(&forward(user(), "beer")).unwrap();
+        assert_eq!(origin, "user:7");
+        assert_eq!(date, Utc.timestamp_opt(1694000000, 0).unwrap());
+    }
+
+    #[test]
+    fn test_hidden_sender_forward() {
+        let message = forward(json!({"forward_sender_name": "Homer"}), "beer");
+        assert_eq!(fingerprint(&message).unwrap().0, "sender:Homer");
+    }
+
+    #[test]
+    fn test_anonymous_admin_forward() {
+        let chat =
+            json!({"forward_from_chat": {"id": -100500, "title": "group", "type": "supergroup"}});
+        assert_eq!(
+            fingerprint(&forward(chat, "beer")).unwrap().0,
+            "chat:-100500"
+        );
+    }
+
+    #[test]
+    fn test_channel_post_forward_is_skipped() {
+        let post = json!({
+            "forward_from_chat": {"id": -100500, "title": "channel", "type": "channel"},
+            "forward_from_message_id": 3
+        });
+        assert!(fingerprint(&forward(post, "beer")).is_none());
+    }
+
+    #[test]
+    fn test_content_matters() {
+        let beer = fingerprint(&forward(user(), "beer")).unwrap();
+        assert_eq!(beer, fingerprint(&forward(user(), "beer")).unwrap());
+        assert_ne!(beer.2, fingerprint(&forward(user(), "wine")).unwrap().2);
+    }
+
+    #[test]
+    fn test_not_a_forward() {
+        let message = supergroup_message("beer", json!({}));
+        assert!(fingerprint(&message).is_none());
+    }
+}
@@ -0,0 +1,101 @@
+// FNV-1a is used since fingerprints are stored, so they must not change between releases
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Number of consecutive words hashed together, so the word order matters as well.
+/// Longer shingles make a single edit change too many of them.
+const SHINGLE_WORDS: usize = 2;
+
+/// Computes the 64-bit SimHash of the text: every bit is the majority vote of the same bits
+/// of hashes of its word shingles, so texts with few edits get fingerprints with few
+/// differing bits. Case, punctuation and whitespace are ignored.
+pub fn simhash(text: &str) -> u64 {
+    let words = words(text);
+    let shingles = words.windows(SHINGLE_WORDS.min(words.len()).max(1));
+
+    let mut votes = [0i64; 64];
+    for shingle in shingles {
+        let hash = fnv1a(&shingle.join(" "));
+        for (bit, vote) in votes.iter_mut().enumerate() {
+            *vote += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+
+    votes
+        .iter()
+        .enumerate()
+        .filter(|(_, vote)| **vote > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+/// Number of letters and digits, texts shorter than a limit are ordinary chatter.
+pub fn meaningful_length(text: &str) -> usize {
+    text.chars().filter(|x| x.is_alphanumeric()).count()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Stable 64-bit hash of the text.
+pub fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::features::dupl_checker::hamming::hamming_distance;
+
+    use super::{fnv1a, meaningful_length, simhash};
+
+    const POST: &str = "Breaking news! The city council has approved the new budget for \
+        the next year. Public transport fares will stay the same, while the funding \
+        for parks and libraries is going to be increased by ten percent. \
+        The mayor thanked everyone who took part in the public hearings.";
+
+    #[test]
+    fn test_copy_pasted_text() {
+        let copy = POST.to_uppercase().replace(". ", ".\n\n");
+        assert_eq!(simhash(POST), simhash(&copy));
+    }
+
+    #[test]
+    fn test_slightly_edited_text() {
+        let edited = POST
+            .replace("Breaking news!", "BREAKING:")
+            .replace("ten", "15");
+        assert!(hamming_distance(simhash(POST), simhash(&edited)) <= 10);
+    }
+
+    #[test]
+    fn test_different_texts() {
+        let other = "Our team has won the match yesterday evening, the final score was \
+            three to one. The coach said the players showed their best game of the season \
+            and the fans celebrated the victory in the streets until late at night.";
+        assert!(hamming_distance(simhash(POST), simhash(other)) > 20);
+    }
+
+    #[test]
+    fn test_short_texts() {
+        assert_eq!(simhash(""), 0);
+        assert_ne!(simhash("beer"), 0);
+        assert_eq!(simhash("beer"), simhash("Beer!"));
+    }
+
+    #[test]
+    fn test_meaningful_length() {
+        assert_eq!(meaningful_length("Beer? Beer!!! 🍺🍺"), 8);
+        assert_eq!(meaningful_length("   \n"), 0);
+    }
+
+    #[test]
+    fn test_fnv1a_is_stable() {
+        assert_eq!(fnv1a(""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a("a"), 0xaf63dc4c8601ec8c);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+use teloxide::types::Me;
+use teloxide::{prelude::*, Bot};
+
+use crate::bot::{ctx::Ctx, settings::effective_settings, utils::topic_id};
+use crate::database::types::TextFingerprint;
+
+use super::hamming::closest;
+use super::simhash::{meaningful_length, simhash};
+use super::{duplicate_window_start, reply_with_alike_or_remember};
+
+/// Responds to the text if an alike one has been posted to the chat before,
+/// otherwise remembers it. Short texts are ignored. Returns whether the response has been sent.
+pub async fn send_media_if_text_posted_before(
+    message: &Message,
+    bot: Bot,
+    me: &Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<bool> {
+    let Some(text) = message
+        .text()
+        .or_else(|| message.caption())
+        .filter(|x| meaningful_length(x) >= ctx.duplicates.min_text_length)
+    else {
+        return Ok(false);
+    };
+    let chat_id = message.chat.id;
+
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        chat_id,
+        topic_id(message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.duplicate_detection_enabled || !settings.text_duplicate_detection_enabled {
+        return Ok(false);
+    }
+
+    let fingerprint = simhash(text);
+    let since = duplicate_window_start(&mut repository, &ctx, chat_id).await?;
+    let known = repository
+        .text_fingerprints_by_chat_id(chat_id.0, since)
+        .await?;
+    let alike = closest(
+        &known,
+        fingerprint,
+        ctx.duplicates.max_text_hash_distance,
+        |x| x.fingerprint as u64,
+    );
+    reply_with_alike_or_remember(
+        message,
+        bot,
+        me,
+        &ctx,
+        &settings,
+        alike.map(|x| x.message_url.as_str()),
+        |mut repository, url| async move { repository.register_text_repost(chat_id.0, &url).await },
+        |mut repository, message_url, (poster_id, poster_name)| async move {
+            repository
+                .insert_text_fingerprint(&TextFingerprint {
+                    chat_id: chat_id.0,
+                    fingerprint: fingerprint as i64,
+                    message_url,
+                    poster_id,
+                    poster_name,
+                })
+                .await
+        },
+    )
+    .await
+}
@@ -1,3 +1,6 @@
+pub mod auto_delete;
+pub mod chats;
 pub mod dupl_checker;
+pub mod reposts;
 pub mod schedule;
 pub mod tag_detector;
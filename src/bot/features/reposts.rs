@@ -0,0 +1,156 @@
+use chrono::{prelude::*, Duration};
+use std::sync::Arc;
+use teloxide::{prelude::*, utils::command::BotCommands, Bot};
+
+use crate::bot::{
+    ctx::Ctx,
+    features::dupl_checker::poster,
+    utils::{send_text, Delivery},
+};
+use crate::database::types::{ReplyOptions, ReposterStats};
+
+const LEADERBOARD_SIZE: i64 = 10;
+
+#[derive(BotCommands, Clone, Debug, PartialEq, Eq)]
+#[command(rename_rule = "lowercase")]
+pub enum RepostCommand {
+    #[command(description = "show top reposters of the chat, `/reposts me` shows your own stats")]
+    Reposts(String),
+}
+
+/// Replies to `/reposts` with the leaderboard of the chat and to `/reposts me` with stats of the sender.
+pub async fn reply_with_repost_stats(
+    message: Message,
+    bot: Bot,
+    ctx: Arc<Ctx>,
+    command: RepostCommand,
+) -> anyhow::Result<()> {
+    let RepostCommand::Reposts(argument) = command;
+    let chat_id = message.chat.id;
+    let mut repository = ctx.repository.clone();
+    let now = Utc::now();
+
+    let text = if argument.trim() == "me" {
+        let (Some(poster_id), poster_name) = poster(&message) else {
+            return Ok(());
+        };
+        let mut counts = Vec::new();
+        for (period, since) in periods(now) {
+            let (reposts, reposted) = repository
+                .user_repost_counts(chat_id.0, poster_id, since)
+                .await?;
+            counts.push((period, reposts, reposted));
+        }
+        user_stats(poster_name.as_deref(), &counts)
+    } else {
+        let mut boards = Vec::new();
+        for (period, since) in periods(now) {
+            let reposters = repository
+                .top_reposters(chat_id.0, since, LEADERBOARD_SIZE)
+                .await?;
+            boards.push((period, reposters));
+        }
+        leaderboard(&boards)
+    };
+
+    send_text(
+        &bot,
+        chat_id,
+        text,
+        Delivery::response_to(&message, ReplyOptions::default()),
+    )
+    .await?;
+    Ok(())
+}
+
+// Names of the periods with their starts, nothing has been reposted before the epoch.
+fn periods(now: DateTime<Utc>) -> [(&'static str, DateTime<Utc>); 3] {
+    [
+        ("this week", now - Duration::weeks(1)),
+        ("this month", now - Duration::days(30)),
+        ("of all time", Utc.timestamp_opt(0, 0).unwrap()),
+    ]
+}
+
+fn leaderboard(boards: &[(&str, Vec<ReposterStats>)]) -> String {
+    boards
+        .iter()
+        .map(|(period, reposters)| {
+            let mut text = format!("Top reposters {period}:");
+            if reposters.is_empty() {
+                text.push_str("\nnobody yet");
+            }
+            for (place, reposter) in reposters.iter().enumerate() {
+                let name = reposter.reposter_name.as_deref().unwrap_or("Someone");
+                text.push_str(&format!(
+                    "\n{}. {name}: {}",
+                    place + 1,
+                    reposter.repost_count
+                ));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn user_stats(name: Option<&str>, counts: &[(&str, i64, i64)]) -> String {
+    let mut text = format!("Reposts of {}:", name.unwrap_or("yours"));
+    for (period, reposts, reposted) in counts {
+        text.push_str(&format!(
+            "\n{period}: {reposts} reposted, {reposted} of the posts reposted by others"
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use teloxide::utils::command::BotCommands;
+
+    use crate::database::types::ReposterStats;
+
+    use super::{leaderboard, user_stats, RepostCommand};
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            RepostCommand::parse("/reposts", "krusty_bot").unwrap(),
+            RepostCommand::Reposts("".to_string())
+        );
+        assert_eq!(
+            RepostCommand::parse("/reposts@krusty_bot me", "krusty_bot").unwrap(),
+            RepostCommand::Reposts("me".to_string())
+        );
+        assert!(RepostCommand::parse("/reposts@other_bot", "krusty_bot").is_err());
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let reposter = |name: Option<&str>, count| ReposterStats {
+            reposter_id: Some(1),
+            reposter_name: name.map(str::to_string),
+            repost_count: count,
+        };
+        let boards = [
+            (
+                "this week",
+                vec![reposter(Some("Homer"), 5), reposter(None, 2)],
+            ),
+            ("of all time", vec![]),
+        ];
+
+        assert_eq!(
+            leaderboard(&boards),
+            "Top reposters this week:\n1. Homer: 5\n2. Someone: 2\n\nTop reposters of all time:\nnobody yet"
+        );
+    }
+
+    #[test]
+    fn test_user_stats() {
+        assert_eq!(
+            user_stats(Some("Homer"), &[("this week", 1, 0), ("of all time", 3, 2)]),
+            "Reposts of Homer:\nthis week: 1 reposted, 0 of the posts reposted by others\nof all time: 3 reposted, 2 of the posts reposted by others"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::anyhow;
+use chrono::{prelude::*, Duration, LocalResult};
+use chrono_tz::Tz;
+use cron::Schedule;
+
+/// Parses the IANA name of a time zone, UTC is used if there is none.
+pub fn parse_time_zone(name: Option<&str>) -> anyhow::Result<Tz> {
+    match name {
+        Some(name) => name
+            .parse()
+            .map_err(|e| anyhow!("Unknown time zone '{name}': {e}")),
+        None => Ok(Tz::UTC),
+    }
+}
+
+/// The first time after the given one which matches the schedule in the time zone.
+/// Local times skipped by a daylight saving time transition never match,
+/// repeated ones match only once.
+pub fn next_fire_time(
+    schedule: &Schedule,
+    time_zone: Tz,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    // the schedule is matched against wall clock times, which UTC ones stand for here,
+    // since `cron` skips repeated local times altogether
+    let local_after = after.with_timezone(&time_zone).naive_local().and_utc();
+    schedule
+        .after(&local_after)
+        .filter_map(|x| match time_zone.from_local_datetime(&x.naive_utc()) {
+            LocalResult::Single(x) => Some(x),
+            LocalResult::Ambiguous(earliest, _) => Some(earliest),
+            LocalResult::None => None,
+        })
+        .map(|x| x.with_timezone(&Utc))
+        .find(|x| *x > after)
+}
+
+/// How long to wait for the time of a one-shot message.
+/// Messages missed by more than the grace period, e.g. while the bot was down, are never sent.
+/// Ones which have become due after `seen_since`, the previous look at the jobs, could not have been
+/// noticed in time, so they are sent late instead.
+pub fn one_shot_delay(
+    run_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    grace_period: Duration,
+    seen_since: DateTime<Utc>,
+) -> Option<std::time::Duration> {
+    if run_at < seen_since && run_at + grace_period < now {
+        return None;
+    }
+    Some((run_at - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{prelude::*, Duration};
+    use chrono_tz::Tz;
+    use cron::Schedule;
+    use std::str::FromStr;
+
+    use super::{next_fire_time, one_shot_delay, parse_time_zone};
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn fire_times(pattern: &str, time_zone: Tz, after: &str, count: usize) -> Vec<DateTime<Utc>> {
+        let schedule = Schedule::from_str(pattern).unwrap();
+        let mut after = utc(after);
+        let mut times = Vec::new();
+        for _ in 0..count {
+            after = next_fire_time(&schedule, time_zone, after).unwrap();
+            times.push(after);
+        }
+        times
+    }
+
+    #[test]
+    fn test_parse_time_zone() {
+        assert_eq!(parse_time_zone(None).unwrap(), Tz::UTC);
+        assert_eq!(
+            parse_time_zone(Some("Europe/Kyiv")).unwrap(),
+            Tz::Europe__Kyiv
+        );
+        assert!(parse_time_zone(Some("Mars/Olympus_Mons")).is_err());
+    }
+
+    #[test]
+    fn test_local_time_is_kept_across_spring_transition() {
+        // clocks go forward from 03:00 to 04:00 in Kyiv on 2024-03-31
+        assert_eq!(
+            fire_times("0 0 8 * * *", Tz::Europe__Kyiv, "2024-03-30T00:00:00Z", 2),
+            [utc("2024-03-30T06:00:00Z"), utc("2024-03-31T05:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_local_time_is_kept_across_autumn_transition() {
+        // clocks go back from 04:00 to 03:00 in Kyiv on 2024-10-27
+        assert_eq!(
+            fire_times("0 0 8 * * *", Tz::Europe__Kyiv, "2024-10-26T00:00:00Z", 2),
+            [utc("2024-10-26T05:00:00Z"), utc("2024-10-27T06:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_skipped_local_time_never_matches() {
+        // 03:30 doesn't exist in Kyiv on 2024-03-31
+        assert_eq!(
+            fire_times("0 30 3 * * *", Tz::Europe__Kyiv, "2024-03-30T12:00:00Z", 1),
+            [utc("2024-04-01T00:30:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_repeated_local_time_matches_once() {
+        // 03:30 happens twice in Kyiv on 2024-10-27
+        assert_eq!(
+            fire_times("0 30 3 * * *", Tz::Europe__Kyiv, "2024-10-26T12:00:00Z", 2),
+            [utc("2024-10-27T00:30:00Z"), utc("2024-10-28T01:30:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_utc_is_default() {
+        assert_eq!(
+            fire_times(
+                "0 0 8 * * *",
+                parse_time_zone(None).unwrap(),
+                "2024-03-30T00:00:00Z",
+                1
+            ),
+            [utc("2024-03-30T08:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn test_one_shot_delay() {
+        let now = utc("2024-01-01T12:00:00Z");
+        let grace_period = Duration::minutes(10);
+        let seen_since = utc("2024-01-01T11:30:00Z");
+
+        assert_eq!(
+            one_shot_delay(utc("2024-01-01T12:05:00Z"), now, grace_period, seen_since),
+            Some(std::time::Duration::from_secs(300))
+        );
+        assert_eq!(
+            one_shot_delay(utc("2024-01-01T11:55:00Z"), now, grace_period, seen_since),
+            Some(std::time::Duration::ZERO)
+        );
+        assert_eq!(
+            one_shot_delay(utc("2024-01-01T11:45:00Z"), now, grace_period, seen_since),
+            Some(std::time::Duration::ZERO)
+        );
+        assert_eq!(
+            one_shot_delay(utc("2024-01-01T11:15:00Z"), now, grace_period, seen_since),
+            None
+        );
+    }
+}
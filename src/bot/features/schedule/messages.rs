@@ -1,10 +1,21 @@
+use chrono::Duration;
+
 use crate::database::repository::AsyncRepository;
+use crate::storage::MediaStores;
 
-use super::scheduler::Scheduler;
+use super::scheduler::{BotJobLauncher, Scheduler};
 
 pub async fn create_scheduler(
     bot: teloxide::Bot,
     repository: AsyncRepository,
+    stores: MediaStores,
+    grace_period: Duration,
 ) -> anyhow::Result<Scheduler> {
-    Scheduler::new(bot, repository).await
+    let launcher = BotJobLauncher {
+        bot,
+        repository: repository.clone(),
+        stores,
+        grace_period,
+    };
+    Ok(Scheduler::new(Box::new(repository), Box::new(launcher)))
 }
@@ -1,2 +1,3 @@
+mod fire_times;
 pub mod messages;
 pub mod scheduler;
\ No newline at end of file
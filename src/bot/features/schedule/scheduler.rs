@@ -1,130 +1,485 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::str::FromStr;
 
 use anyhow::anyhow;
-use futures::future;
-use teloxide::{types::ChatId, Bot};
-use tokio_cron_scheduler::{Job, JobScheduler};
-use uuid::Uuid;
+use async_trait::async_trait;
+use chrono::{prelude::*, Duration};
+use chrono_tz::Tz;
+use cron::Schedule;
+use mockall::automock;
+use teloxide::{types::ChatId, ApiError, Bot, RequestError};
+use tokio::task::JoinHandle;
 
 use crate::{
-    bot::utils::{choose_random_media_info, send_media},
-    database::{repository::AsyncRepository, types::CroneJob},
+    bot::{
+        features::auto_delete::delete_reply_later,
+        utils::{choose_random_media_info, send_media, Caption, Delivery},
+    },
+    database::{
+        repository::AsyncRepository,
+        types::{CroneJob, MediaInfo},
+    },
+    formatting::validate,
+    storage::MediaStores,
 };
 
+use super::fire_times::{next_fire_time, one_shot_delay, parse_time_zone};
+
+/// Where the jobs to schedule come from.
+#[automock]
+#[async_trait]
+pub trait CronJobSource: Send + Sync {
+    async fn cron_jobs(&mut self) -> anyhow::Result<Vec<CroneJob>>;
+}
+
+#[async_trait]
+impl CronJobSource for AsyncRepository {
+    async fn cron_jobs(&mut self) -> anyhow::Result<Vec<CroneJob>> {
+        AsyncRepository::cron_jobs(self).await
+    }
+}
+
+/// Starts a task which sends messages of the job when they are due.
+#[automock]
+pub trait JobLauncher: Send + Sync {
+    /// Jobs which have become due after `seen_since` are missed by the scheduler, not by the bot.
+    fn launch(
+        &self,
+        cron_job: CroneJob,
+        seen_since: DateTime<Utc>,
+    ) -> anyhow::Result<JoinHandle<()>>;
+}
+
+pub struct BotJobLauncher {
+    pub bot: Bot,
+    pub repository: AsyncRepository,
+    pub stores: MediaStores,
+    /// One-shot messages missed by more than that are never sent.
+    pub grace_period: Duration,
+}
+
+impl JobLauncher for BotJobLauncher {
+    fn launch(
+        &self,
+        cron_job: CroneJob,
+        seen_since: DateTime<Utc>,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        if let Some(caption) = &cron_job.caption {
+            validate(caption, &cron_job.parse_mode).map_err(|e| anyhow!("invalid caption: {e}"))?;
+        }
+
+        match (&cron_job.pattern, cron_job.run_at) {
+            (Some(pattern), _) => {
+                let schedule = Schedule::from_str(pattern)?;
+                let time_zone = parse_time_zone(cron_job.time_zone.as_deref())?;
+                Ok(tokio::spawn(run_cron_job(
+                    self.bot.clone(),
+                    self.repository.clone(),
+                    self.stores.clone(),
+                    cron_job,
+                    schedule,
+                    time_zone,
+                )))
+            }
+            (None, Some(run_at)) => Ok(tokio::spawn(run_one_shot_job(
+                self.bot.clone(),
+                self.repository.clone(),
+                self.stores.clone(),
+                cron_job,
+                run_at,
+                self.grace_period,
+                seen_since,
+            ))),
+            (None, None) => Err(anyhow!("neither a pattern nor a time to run at")),
+        }
+    }
+}
+
+struct ScheduledJob {
+    // the row the job has been launched with, so that edits of the row are noticed
+    cron_job: CroneJob,
+    task: JoinHandle<()>,
+}
+
 pub struct Scheduler {
-    pub(in crate::bot::features::schedule) inner: JobScheduler,
-    bot: Bot,
-    ids_to_uids: HashMap<i32, Uuid>,
-    repository: AsyncRepository,
+    source: Box<dyn CronJobSource>,
+    launcher: Box<dyn JobLauncher>,
+    // every job waits for its fire times in a task of its own
+    jobs: HashMap<i32, ScheduledJob>,
+    synced_at: Option<DateTime<Utc>>,
 }
 
 impl Scheduler {
     pub async fn start(&mut self) -> anyhow::Result<()> {
-        self.sync().await?;
-        self.inner.start().await?;
-
-        Ok(())
+        self.sync().await
     }
 
+    /// Launches new jobs, stops removed ones and relaunches the ones which have been edited.
     pub async fn sync(&mut self) -> anyhow::Result<()> {
-        let cron_jobs = self.repository.cron_jobs().await?;
-        let job_ids: HashSet<_> = cron_jobs.iter().map(|x| x.id).collect();
+        let now = Utc::now();
+        let cron_jobs = self.source.cron_jobs().await?;
+        let seen_since = self.synced_at.replace(now).unwrap_or(now);
 
-        let scheduled_job_ids: HashSet<_> = self.ids_to_uids.keys().cloned().collect();
+        self.jobs.retain(|id, job| {
+            let current = cron_jobs.iter().find(|x| x.id == *id);
+            if current == Some(&job.cron_job) {
+                return true;
+            }
 
-        let obsolete_job_ids: Vec<_> = scheduled_job_ids.difference(&job_ids).collect();
-        let remove_task_iter = obsolete_job_ids.iter().map(|id| {
-            let uuid = self.ids_to_uids.get(id).unwrap();
-            self.inner.remove(uuid)
+            match current {
+                Some(_) => log::info!("Cron job #{id} has been edited, relaunching it"),
+                None => log::info!("Cron job #{id} has been removed, stopping it"),
+            }
+            job.task.abort();
+            false
         });
 
-        for (e, uuid) in future::join_all(remove_task_iter)
-            .await
-            .iter()
-            .zip(obsolete_job_ids)
-            .filter(|(r, _)| r.is_err())
-            .map(|(r, uuid)| (r.as_ref().unwrap_err(), uuid))
-        {
-            log::error!("Failed to remove cron job with uuid '{uuid}': '{e}'");
-        }
-
-        let job_ids_to_add: HashSet<_> = job_ids.difference(&scheduled_job_ids).collect();
-        for cj in cron_jobs
-            .into_iter()
-            .filter(|x| job_ids_to_add.contains(&x.id))
-        {
-            let bot: teloxide::Bot = self.bot.clone();
-            let repository = self.repository.clone();
-            let pattern = cj.pattern.clone();
-            let id = cj.id;
-
-            let job = Job::new_async(pattern.as_str(), move |_, _| {
-                let bot = bot.clone();
-                let repository = repository.clone();
-                let cj = cj.clone();
-
-                Box::pin(async move {
-                    if let Err(e) = send_scheduled_message(bot, repository, cj).await {
-                        log::error!("Failed to send scheduled message: '{e}'");
-                    }
-                })
-            });
-            if let Err(e) = job {
-                log::error!("Failed to create cron job '{pattern}': '{e}'");
+        for cj in cron_jobs {
+            if self.jobs.contains_key(&cj.id) {
                 continue;
             }
 
-            match self.inner.add(job.unwrap()).await {
-                Ok(uuid) => {
-                    self.ids_to_uids.insert(id, uuid);
+            match self.launcher.launch(cj.clone(), seen_since) {
+                Ok(task) => {
+                    self.jobs.insert(cj.id, ScheduledJob { cron_job: cj, task });
                 }
-                Err(e) => log::error!("Failed to add cron job '{pattern}': '{e}'"),
-            };
+                Err(e) => log::error!("Failed to create cron job '{}': '{e}'", cj.schedule()),
+            }
         }
 
         Ok(())
     }
 
-    pub(in crate::bot::features::schedule) async fn new(
-        bot: Bot,
-        repository: AsyncRepository,
-    ) -> anyhow::Result<Self> {
-        Ok(Self {
-            inner: JobScheduler::new().await?,
-            bot,
-            ids_to_uids: HashMap::new(),
-            repository,
-        })
+    pub(in crate::bot::features::schedule) fn new(
+        source: Box<dyn CronJobSource>,
+        launcher: Box<dyn JobLauncher>,
+    ) -> Self {
+        Self {
+            source,
+            launcher,
+            jobs: HashMap::new(),
+            synced_at: None,
+        }
+    }
+}
+
+async fn run_cron_job(
+    bot: Bot,
+    repository: AsyncRepository,
+    stores: MediaStores,
+    cron_job: CroneJob,
+    schedule: Schedule,
+    time_zone: Tz,
+) {
+    let mut after = Utc::now();
+    while let Some(fire_time) = next_fire_time(&schedule, time_zone, after) {
+        let delay = (fire_time - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(delay).await;
+
+        let sent = send_scheduled_message(
+            bot.clone(),
+            repository.clone(),
+            stores.clone(),
+            cron_job.clone(),
+        )
+        .await;
+        if let Err(e) = sent {
+            log::error!("Failed to send scheduled message: '{e}'");
+        }
+        // fire times missed while sending are skipped
+        after = fire_time.max(Utc::now());
+    }
+}
+
+// The job is done once it's due, even if the message has failed to be sent.
+async fn run_one_shot_job(
+    bot: Bot,
+    mut repository: AsyncRepository,
+    stores: MediaStores,
+    cron_job: CroneJob,
+    run_at: DateTime<Utc>,
+    grace_period: Duration,
+    seen_since: DateTime<Utc>,
+) {
+    let id = cron_job.id;
+    let Some(delay) = one_shot_delay(run_at, Utc::now(), grace_period, seen_since) else {
+        log::warn!("Scheduled message #{id} missed at {run_at} is skipped");
+        if let Err(e) = repository.mark_cron_job_skipped(id).await {
+            log::error!("Failed to mark scheduled message #{id} as skipped: '{e}'");
+        }
+        return;
+    };
+
+    tokio::time::sleep(delay).await;
+    let sent = send_scheduled_message(bot, repository.clone(), stores, cron_job).await;
+    if let Err(e) = sent {
+        log::error!("Failed to send scheduled message: '{e}'");
+    }
+
+    if let Err(e) = repository.mark_cron_job_sent(id).await {
+        log::error!("Failed to mark scheduled message #{id} as sent: '{e}'");
     }
 }
 
 async fn send_scheduled_message(
     bot: teloxide::Bot,
     mut repository: AsyncRepository,
+    stores: MediaStores,
     cron_job: CroneJob,
 ) -> anyhow::Result<()> {
-    if cron_job.chat_id.is_none() {
-        return Err(anyhow!(
-            "Sending messages to all discovered chats are not supported currently"
-        ));
-    }
-
+    let schedule = cron_job.schedule();
     let media_infos = repository.media_info_by_cron_job_id(cron_job.id).await?;
     let media_info = choose_random_media_info(&media_infos);
     if media_info.is_none() {
-        return Err(anyhow!(
-            "No media found for cron job '{}'",
-            cron_job.pattern
-        ));
+        return Err(anyhow!("No media found for cron job '{schedule}'"));
     }
 
-    send_media(
-        media_info.unwrap(),
+    let media_info = media_info.unwrap();
+    let caption = cron_job.caption.map(|text| Caption {
+        text,
+        parse_mode: cron_job.parse_mode,
+    });
+
+    let Some(chat_id) = cron_job.chat_id else {
+        // topics differ from chat to chat, so broadcasts go to General ones
+        let chat_ids = repository.broadcast_chat_ids().await?;
+        let mut failures = 0;
+        for chat_id in chat_ids.iter().map(|x| ChatId(*x)) {
+            let sent = send_to_chat(
+                bot.clone(),
+                &mut repository,
+                &stores,
+                media_info,
+                chat_id,
+                None,
+                caption.clone(),
+            )
+            .await;
+            if let Err(e) = sent {
+                failures += 1;
+                log::error!("Failed to broadcast cron job '{schedule}' to chat {chat_id}: '{e}'");
+                if is_chat_gone(&e) {
+                    if let Err(e) = repository.deactivate_chat(chat_id.0).await {
+                        log::error!("Failed to deactivate chat {chat_id}: '{e}'");
+                    }
+                }
+            }
+        }
+        log::info!(
+            "Broadcast cron job '{schedule}' to {} of {} chats",
+            chat_ids.len() - failures,
+            chat_ids.len()
+        );
+        return Ok(());
+    };
+
+    send_to_chat(
+        bot,
         &mut repository,
+        &stores,
+        media_info,
+        ChatId(chat_id),
+        cron_job.message_thread_id,
+        caption,
+    )
+    .await
+}
+
+async fn send_to_chat(
+    bot: teloxide::Bot,
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    media_info: &MediaInfo,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    caption: Option<Caption>,
+) -> anyhow::Result<()> {
+    let message_ids = send_media(
+        media_info,
+        repository,
+        stores,
+        bot.clone(),
+        chat_id,
+        Delivery {
+            thread_id,
+            ..Default::default()
+        },
+        caption,
+    )
+    .await?;
+
+    delete_reply_later(
         bot,
-        ChatId(cron_job.chat_id.unwrap()),
+        repository,
+        chat_id,
+        &message_ids,
+        &media_info.name,
         None,
-        cron_job.caption,
     )
     .await
 }
+
+// The bot can't send anything to the chat anymore, so it shouldn't try again.
+fn is_chat_gone(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<RequestError>(),
+        Some(RequestError::Api(
+            ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::ChatNotFound
+                | ApiError::GroupDeactivated
+        ))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::Utc;
+    use mockall::Sequence;
+    use teloxide::{ApiError, RequestError};
+
+    use crate::database::types::{CroneJob, TextParseMode};
+
+    use super::{is_chat_gone, MockCronJobSource, MockJobLauncher, Scheduler};
+
+    fn cron_job(id: i32, pattern: &str) -> CroneJob {
+        CroneJob {
+            id,
+            pattern: Some(pattern.to_string()),
+            chat_id: Some(1),
+            caption: None,
+            description: None,
+            parse_mode: TextParseMode::None,
+            message_thread_id: None,
+            time_zone: None,
+            run_at: None,
+        }
+    }
+
+    /// A scheduler which sees `snapshots` on consecutive syncs and records launched jobs.
+    fn scheduler(snapshots: Vec<Vec<CroneJob>>) -> (Scheduler, Arc<Mutex<Vec<CroneJob>>>) {
+        let mut source = MockCronJobSource::new();
+        let mut seq = Sequence::new();
+        for snapshot in snapshots {
+            source
+                .expect_cron_jobs()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || Ok(snapshot.clone()));
+        }
+
+        let launched = Arc::new(Mutex::new(vec![]));
+        let mut launcher = MockJobLauncher::new();
+        let l = launched.clone();
+        launcher.expect_launch().returning(move |cj, _| {
+            l.lock().unwrap().push(cj);
+            Ok(tokio::spawn(futures::future::pending()))
+        });
+
+        (
+            Scheduler::new(Box::new(source), Box::new(launcher)),
+            launched,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sync_adds_new_jobs() {
+        let (mut scheduler, launched) = scheduler(vec![
+            vec![cron_job(1, "0 0 9 * * *")],
+            vec![cron_job(1, "0 0 9 * * *"), cron_job(2, "0 0 18 * * *")],
+        ]);
+
+        scheduler.sync().await.unwrap();
+        scheduler.sync().await.unwrap();
+
+        let launched = launched.lock().unwrap();
+        assert_eq!(launched.iter().map(|x| x.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(scheduler.jobs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_tells_when_jobs_have_been_seen_last() {
+        let mut source = MockCronJobSource::new();
+        let mut seq = Sequence::new();
+        for snapshot in [
+            vec![cron_job(1, "0 0 9 * * *")],
+            vec![cron_job(1, "0 0 9 * * *"), cron_job(2, "0 0 18 * * *")],
+        ] {
+            source
+                .expect_cron_jobs()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || Ok(snapshot.clone()));
+        }
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut launcher = MockJobLauncher::new();
+        let s = seen.clone();
+        launcher.expect_launch().returning(move |_, seen_since| {
+            s.lock().unwrap().push(seen_since);
+            Ok(tokio::spawn(futures::future::pending()))
+        });
+        let mut scheduler = Scheduler::new(Box::new(source), Box::new(launcher));
+
+        let before = Utc::now();
+        scheduler.sync().await.unwrap();
+        scheduler.sync().await.unwrap();
+
+        // jobs added later are new to the scheduler since the first sync
+        let seen = seen.lock().unwrap();
+        assert!(seen[0] >= before);
+        assert_eq!(seen[0], seen[1]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_removes_deleted_jobs() {
+        let (mut scheduler, launched) = scheduler(vec![
+            vec![cron_job(1, "0 0 9 * * *"), cron_job(2, "0 0 18 * * *")],
+            vec![cron_job(2, "0 0 18 * * *")],
+        ]);
+
+        scheduler.sync().await.unwrap();
+        let removed = scheduler.jobs[&1].task.abort_handle();
+        scheduler.sync().await.unwrap();
+        tokio::task::yield_now().await;
+
+        assert_eq!(launched.lock().unwrap().len(), 2);
+        assert!(removed.is_finished());
+        assert_eq!(scheduler.jobs.keys().collect::<Vec<_>>(), [&2]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_relaunches_edited_jobs() {
+        let mut edited = cron_job(1, "0 0 9 * * *");
+        edited.time_zone = Some("Europe/Kyiv".to_string());
+        let (mut scheduler, launched) = scheduler(vec![
+            vec![cron_job(1, "0 0 9 * * *"), cron_job(2, "0 0 18 * * *")],
+            vec![edited.clone(), cron_job(2, "0 0 18 * * *")],
+        ]);
+
+        scheduler.sync().await.unwrap();
+        let outdated = scheduler.jobs[&1].task.abort_handle();
+        let untouched = scheduler.jobs[&2].task.abort_handle();
+        scheduler.sync().await.unwrap();
+        tokio::task::yield_now().await;
+
+        assert_eq!(launched.lock().unwrap().last(), Some(&edited));
+        assert_eq!(launched.lock().unwrap().len(), 3);
+        assert!(outdated.is_finished());
+        assert!(!untouched.is_finished());
+        assert_eq!(scheduler.jobs[&1].cron_job, edited);
+    }
+
+    #[test]
+    fn test_is_chat_gone() {
+        assert!(is_chat_gone(&RequestError::Api(ApiError::BotKicked).into()));
+        assert!(is_chat_gone(
+            &RequestError::Api(ApiError::ChatNotFound).into()
+        ));
+        assert!(!is_chat_gone(
+            &RequestError::Api(ApiError::MessageTextIsEmpty).into()
+        ));
+        assert!(!is_chat_gone(&anyhow::anyhow!("No media")));
+    }
+}
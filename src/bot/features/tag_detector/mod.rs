@@ -8,11 +8,13 @@ use rand::Rng;
 use std::sync::Arc;
 use teloxide::{prelude::*, Bot};
 
-use crate::bot::cache::media_info_by_tag_text;
+use crate::bot::cache::{feature_reply_options, media_info_by_tag_text, tag_reply_options_by_text};
 use crate::bot::ctx::Ctx;
-use crate::bot::utils::{choose_random_media_info, is_time_passed, send_media};
+use crate::bot::features::auto_delete::delete_reply_later;
+use crate::bot::settings::effective_settings;
+use crate::bot::utils::{choose_random_media_info, is_time_passed, send_media, topic_id, Delivery};
 use crate::database::repository::AsyncRepository;
-use crate::database::types::MediaInfo;
+use crate::database::types::{MediaFeatureType, MediaInfo};
 
 use self::similarity::recognize_tag_in_tokens;
 use self::tag_provider::RepositoryTagProvider;
@@ -23,11 +25,23 @@ pub async fn send_media_on_text_trigger(
     bot: Bot,
     ctx: Arc<Ctx>,
 ) -> anyhow::Result<()> {
+    let mut repository = ctx.repository.clone();
+    let settings = effective_settings(
+        &mut repository,
+        message.chat.id,
+        topic_id(&message),
+        ctx.media_timeout,
+    )
+    .await?;
+    if !settings.text_trigger_enabled {
+        return Ok(());
+    }
+
     {
         log::debug!("Locking text trigger chat mutex");
         let mut chat_times = ctx.text_trigger_timestamps.lock().await;
-        if let Some(time) = chat_times.get(&message.chat.id) {
-            if !is_time_passed(time, &ctx.media_timeout) {
+        if let Some(time) = chat_times.get(&settings.cooldown_key) {
+            if !is_time_passed(time, &settings.cooldown) {
                 log::debug!(
                     "There is a timeout for chat '{}', skipping",
                     message.chat.id
@@ -35,28 +49,41 @@ pub async fn send_media_on_text_trigger(
                 return Ok(());
             }
         }
-        chat_times.insert(message.chat.id, Utc::now());
+        chat_times.insert(settings.cooldown_key, Utc::now());
     }
 
-    let mut repository = ctx.repository.clone();
     let tag_provider = RepositoryTagProvider::new(&mut repository).await?;
 
     let chat_id = message.chat.id;
-    let message_id = message.id;
+    let trigger = message.clone();
     let token_provider = MessageTokenProvider::new(message);
     if let Some(tag) =
         recognize_tag_in_tokens(&token_provider, &tag_provider, &ctx.similarity_threshold)
     {
         if let Some(media) = get_random_media_info_for_tag(&tag, &mut repository).await {
             if should_media_be_sent(&ctx.media_being_sent_chance) {
-                send_media(
+                // options of the tag win over the ones of the feature
+                let options = tag_reply_options_by_text(&mut repository, &tag).await?.or(
+                    feature_reply_options(&mut repository, MediaFeatureType::TextTrigger).await?,
+                );
+                let message_ids = send_media(
                     &media,
                     &mut repository,
-                    bot,
+                    &ctx.media_stores,
+                    bot.clone(),
                     chat_id,
-                    Some(message_id),
+                    Delivery::response_to(&trigger, options),
                     None,
                 )
+                .await?;
+                delete_reply_later(
+                    bot,
+                    &mut repository,
+                    chat_id,
+                    &message_ids,
+                    &media.name,
+                    Some(&tag),
+                )
                 .await?
             } else {
                 log::debug!("Match was found, but omitted due to low chance");
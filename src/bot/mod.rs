@@ -1,6 +1,11 @@
+mod byte_budget_cache;
 mod cache;
 mod ctx;
 mod features;
+mod script;
+mod settings;
+#[cfg(test)]
+mod test_utils;
 mod utils;
 
 use chrono::Duration;
@@ -10,29 +15,68 @@ use futures::future;
 use percentage::{PercentageDecimal, PercentageInteger};
 use std::sync::Arc;
 use teloxide::prelude::*;
+use teloxide::types::{ChatMemberUpdated, Me};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::database::notifications::{listen_for_table_changes, TableChange};
+use crate::storage::MediaStores;
 
 use self::ctx::Ctx;
-use self::features::dupl_checker::send_media_if_forwarded_before;
+use self::features::auto_delete::reschedule_pending_deletions;
+use self::features::chats::{remember_chat_of_message, update_chat_membership};
+use self::features::dupl_checker::{
+    prune_duplicate_candidates_periodically, send_media_if_forwarded_before,
+    send_media_if_link_posted_before, send_media_if_photo_posted_before,
+    send_media_if_sender_forward_posted_before, send_media_if_text_posted_before,
+};
+use self::features::reposts::{reply_with_repost_stats, RepostCommand};
 use self::features::schedule::messages::create_scheduler;
 use self::features::tag_detector::send_media_on_text_trigger;
+use self::script::shutdown_token;
 use self::utils::is_time_passed;
 
+pub use self::cache::{configure_media_data_cache, media_data_cache_stats, MediaCacheStats};
+pub use self::features::dupl_checker::{DuplicateConfig, DEFAULT_CAPTION_TEMPLATE};
+
+#[allow(clippy::too_many_arguments)]
 pub async fn start_bot(
     bot: teloxide::Bot,
     media_timeout: Duration,
     ignore_message_older_than: Duration,
     media_being_sent_chance: PercentageInteger,
     similarity_threshold: PercentageDecimal,
+    duplicates: DuplicateConfig,
+    scheduled_message_grace_period: Duration,
+    db_url: String,
     pool: Pool<AsyncDieselConnectionManager<diesel_async::AsyncPgConnection>>,
+    media_stores: MediaStores,
 ) {
     let ctx = Arc::new(Ctx::new(
         media_timeout,
         media_being_sent_chance,
         similarity_threshold,
+        duplicates,
         pool,
+        media_stores,
     ));
 
-    let maybe_scheduler = create_scheduler(bot.clone(), ctx.repository.clone()).await;
+    match reschedule_pending_deletions(bot.clone(), ctx.repository.clone()).await {
+        Ok(count) => log::info!("Rescheduled {count} pending message deletions"),
+        Err(e) => log::error!("Failed to reschedule pending message deletions: '{e}'"),
+    }
+
+    let maybe_scheduler = create_scheduler(
+        bot.clone(),
+        ctx.repository.clone(),
+        ctx.media_stores.clone(),
+        scheduled_message_grace_period,
+    )
+    .await;
+    let (table_changes, _) = broadcast::channel(64);
+    let mut scheduler_changes = table_changes.subscribe();
+    tokio::spawn(invalidate_cache_on_table_changes(table_changes.subscribe()));
+    tokio::spawn(listen_for_table_changes(db_url, table_changes));
+
     let scheduler_task = tokio::spawn(async move {
         if let Err(e) = maybe_scheduler {
             log::error!("Failed to create message scheduler: '{e}'");
@@ -45,30 +89,70 @@ pub async fn start_bot(
             return;
         }
 
+        // polling is kept in case notifications have been missed while reconnecting
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
         interval.tick().await; // ommit first immidiate tick
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                change = scheduler_changes.recv() => match change {
+                    Ok(TableChange::Table(table)) if table != "cron_jobs" && table != "chats" => {
+                        continue
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+            }
             if let Err(e) = scheduler.sync().await {
                 log::error!("Failed to update jobs list in scheduler: '{e}'");
             }
         }
     });
 
+    tokio::spawn(prune_duplicate_candidates_periodically(
+        ctx.repository.clone(),
+        ctx.duplicates.default_window_days,
+    ));
+
+    tokio::spawn(async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for shutdown signal: '{e}'");
+            return;
+        }
+        shutdown_token().cancel();
+    });
+
     let message_listener_task = tokio::spawn(async move {
-        let handler = Update::filter_message()
-            .filter(|msg: Message, _: Arc<Ctx>| msg.chat.is_supergroup())
+        let handler = dptree::entry()
             .branch(
-                dptree::filter(|msg: Message, _: Arc<Ctx>| {
-                    msg.forward_from_message_id().is_some() && msg.forward_from_chat().is_some()
-                })
-                .endpoint(send_media_if_forwarded_before),
+                Update::filter_my_chat_member()
+                    .filter(|update: ChatMemberUpdated, _: Arc<Ctx>| update.chat.is_supergroup())
+                    .endpoint(update_chat_membership),
             )
             .branch(
-                dptree::filter(move |msg: Message, _: Arc<Ctx>| {
-                    !is_time_passed(&msg.date, &ignore_message_older_than)
-                })
-                .endpoint(send_media_on_text_trigger),
+                Update::filter_message()
+                    .filter(|msg: Message, _: Arc<Ctx>| msg.chat.is_supergroup())
+                    .inspect_async(remember_chat_of_message)
+                    .branch(
+                        dptree::filter(move |msg: Message, _: Arc<Ctx>| {
+                            !is_time_passed(&msg.date, &ignore_message_older_than)
+                        })
+                        .filter_command::<RepostCommand>()
+                        .endpoint(reply_with_repost_stats),
+                    )
+                    .branch(
+                        dptree::filter(|msg: Message, _: Arc<Ctx>| {
+                            msg.forward_from_message_id().is_some()
+                                && msg.forward_from_chat().is_some()
+                        })
+                        .endpoint(send_media_if_forwarded_before),
+                    )
+                    .branch(
+                        dptree::filter(move |msg: Message, _: Arc<Ctx>| {
+                            !is_time_passed(&msg.date, &ignore_message_older_than)
+                        })
+                        .endpoint(check_duplicates_then_text),
+                    ),
             );
         Dispatcher::builder(bot.clone(), handler)
             .dependencies(dptree::deps![ctx])
@@ -80,3 +164,34 @@ pub async fn start_bot(
 
     let _ = future::join(scheduler_task, message_listener_task).await;
 }
+
+async fn invalidate_cache_on_table_changes(mut changes: broadcast::Receiver<TableChange>) {
+    loop {
+        match changes.recv().await {
+            Ok(TableChange::Table(table)) => cache::invalidate(&table).await,
+            Ok(TableChange::Any) => cache::invalidate_all().await,
+            Err(RecvError::Lagged(count)) => {
+                log::warn!("Missed {count} table changes, dropping every cached value");
+                cache::invalidate_all().await;
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+// Texts and captions may contain hot words, but a duplicate is worth only one response.
+async fn check_duplicates_then_text(
+    message: Message,
+    bot: Bot,
+    me: Me,
+    ctx: Arc<Ctx>,
+) -> anyhow::Result<()> {
+    if send_media_if_sender_forward_posted_before(&message, bot.clone(), &me, ctx.clone()).await?
+        || send_media_if_photo_posted_before(&message, bot.clone(), &me, ctx.clone()).await?
+        || send_media_if_link_posted_before(&message, bot.clone(), &me, ctx.clone()).await?
+        || send_media_if_text_posted_before(&message, bot.clone(), &me, ctx.clone()).await?
+    {
+        return Ok(());
+    }
+    send_media_on_text_trigger(message, bot, ctx).await
+}
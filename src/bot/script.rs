@@ -0,0 +1,419 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use mockall::automock;
+use rand::Rng;
+use std::sync::LazyLock;
+use std::time::Duration;
+use teloxide::requests::HasPayload;
+use teloxide::types::{ChatAction, MessageId};
+use teloxide::{prelude::*, Bot};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::repository::AsyncRepository;
+use crate::database::types::{self, MediaInfo, MediaType, ScriptStepKind};
+use crate::storage::MediaStores;
+
+use super::cache::media_script_steps_by_name;
+use super::utils::{send_media, Caption, Delivery};
+
+static SHUTDOWN: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+
+/// Cancelled once the bot is shutting down, so the scripts which are still running stop early.
+pub fn shutdown_token() -> &'static CancellationToken {
+    &SHUTDOWN
+}
+
+#[derive(Clone)]
+pub enum Step {
+    ChatAction(ChatAction),
+    Delay { min: Duration, max: Duration },
+    Media(MediaInfo),
+}
+
+impl TryFrom<types::ScriptStep> for Step {
+    type Error = anyhow::Error;
+
+    fn try_from(step: types::ScriptStep) -> Result<Self, Self::Error> {
+        match step.kind {
+            ScriptStepKind::ChatAction => {
+                let action = step.chat_action.unwrap_or_default();
+                parse_chat_action(&action)
+                    .map(Step::ChatAction)
+                    .ok_or_else(|| anyhow!("unknown chat action '{action}'"))
+            }
+            ScriptStepKind::Delay => {
+                let min = step.min_delay_ms.unwrap_or_default();
+                let max = step.max_delay_ms.unwrap_or(min);
+                if min < 0 || max < min {
+                    return Err(anyhow!("invalid delay range {min}..={max} ms"));
+                }
+                Ok(Step::Delay {
+                    min: Duration::from_millis(min as u64),
+                    max: Duration::from_millis(max as u64),
+                })
+            }
+            ScriptStepKind::Media => {
+                match (step.media_name, step.media_type, step.media_parse_mode) {
+                    (Some(_), Some(MediaType::Script), _) => {
+                        Err(anyhow!("scripts can't be nested"))
+                    }
+                    (Some(name), Some(type_), Some(parse_mode)) => Ok(Step::Media(MediaInfo {
+                        name,
+                        type_,
+                        caption: step.media_caption,
+                        parse_mode,
+                    })),
+                    _ => Err(anyhow!("media step has no media")),
+                }
+            }
+        }
+    }
+}
+
+fn parse_chat_action(s: &str) -> Option<ChatAction> {
+    match s {
+        "typing" => Some(ChatAction::Typing),
+        "upload_photo" => Some(ChatAction::UploadPhoto),
+        "record_video" => Some(ChatAction::RecordVideo),
+        "upload_video" => Some(ChatAction::UploadVideo),
+        "record_voice" => Some(ChatAction::RecordVoice),
+        "upload_voice" => Some(ChatAction::UploadVoice),
+        "upload_document" => Some(ChatAction::UploadDocument),
+        "find_location" => Some(ChatAction::FindLocation),
+        "record_video_note" => Some(ChatAction::RecordVideoNote),
+        "upload_video_note" => Some(ChatAction::UploadVideoNote),
+        _ => None,
+    }
+}
+
+#[automock]
+#[async_trait]
+pub trait ScriptExecutor: Send + Sync {
+    async fn send_chat_action(&self, action: ChatAction) -> anyhow::Result<()>;
+    async fn sleep(&self, duration: Duration);
+    /// The first media of a script replies to the trigger message and gets the caption.
+    async fn send_media(&self, media: &MediaInfo, is_first: bool)
+        -> anyhow::Result<Vec<MessageId>>;
+}
+
+struct BotScriptExecutor {
+    bot: Bot,
+    chat_id: ChatId,
+    delivery: Delivery,
+    caption: Option<Caption>,
+    repository: AsyncRepository,
+    stores: MediaStores,
+}
+
+#[async_trait]
+impl ScriptExecutor for BotScriptExecutor {
+    async fn send_chat_action(&self, action: ChatAction) -> anyhow::Result<()> {
+        let mut r = self.bot.send_chat_action(self.chat_id, action);
+        r.payload_mut().message_thread_id = self.delivery.thread_id;
+        r.await?;
+        Ok(())
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    async fn send_media(
+        &self,
+        media: &MediaInfo,
+        is_first: bool,
+    ) -> anyhow::Result<Vec<MessageId>> {
+        send_media(
+            media,
+            &mut self.repository.clone(),
+            &self.stores,
+            self.bot.clone(),
+            self.chat_id,
+            if is_first {
+                self.delivery
+            } else {
+                self.delivery.without_reply()
+            },
+            self.caption.clone().filter(|_| is_first),
+        )
+        .await
+    }
+}
+
+pub async fn send_script(
+    media: &MediaInfo,
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    bot: Bot,
+    chat_id: ChatId,
+    delivery: Delivery,
+    caption: Option<Caption>,
+) -> anyhow::Result<Vec<MessageId>> {
+    let steps = media_script_steps_by_name(repository, &media.name)
+        .await?
+        .into_iter()
+        .map(Step::try_from)
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| anyhow!("Script '{}' is malformed: {e}", media.name))?;
+
+    let executor = BotScriptExecutor {
+        bot,
+        chat_id,
+        delivery,
+        caption,
+        repository: repository.clone(),
+        stores: stores.clone(),
+    };
+    run_script(&steps, &executor, shutdown_token()).await
+}
+
+/// Runs the steps one by one and returns ids of all the sent messages.
+pub async fn run_script(
+    steps: &[Step],
+    executor: &impl ScriptExecutor,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<Vec<MessageId>> {
+    let mut message_ids = Vec::new();
+    let mut is_first_media = true;
+    for step in steps {
+        tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => return Err(anyhow!("Script is cancelled due to shutdown")),
+            r = run_step(step, executor, is_first_media) => message_ids.extend(r?),
+        }
+
+        if matches!(step, Step::Media(_)) {
+            is_first_media = false;
+        }
+    }
+
+    Ok(message_ids)
+}
+
+pub async fn run_step(
+    step: &Step,
+    executor: &impl ScriptExecutor,
+    is_first_media: bool,
+) -> anyhow::Result<Vec<MessageId>> {
+    match step {
+        Step::ChatAction(action) => {
+            executor.send_chat_action(*action).await?;
+            Ok(vec![])
+        }
+        Step::Delay { min, max } => {
+            executor.sleep(random_delay(*min, *max)).await;
+            Ok(vec![])
+        }
+        Step::Media(media) => executor.send_media(media, is_first_media).await,
+    }
+}
+
+fn random_delay(min: Duration, max: Duration) -> Duration {
+    if min >= max {
+        return min;
+    }
+    rand::thread_rng().gen_range(min..=max)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::{predicate::eq, Sequence};
+    use std::time::Duration;
+    use teloxide::types::{ChatAction, MessageId};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::database::types::{self, MediaInfo, MediaType, ScriptStepKind, TextParseMode};
+
+    use super::{run_script, run_step, MockScriptExecutor, Step};
+
+    fn media(name: &str, type_: MediaType) -> MediaInfo {
+        MediaInfo {
+            name: name.to_string(),
+            type_,
+            caption: None,
+            parse_mode: TextParseMode::None,
+        }
+    }
+
+    fn row(kind: ScriptStepKind) -> types::ScriptStep {
+        types::ScriptStep {
+            kind,
+            chat_action: None,
+            min_delay_ms: None,
+            max_delay_ms: None,
+            media_name: None,
+            media_type: None,
+            media_caption: None,
+            media_parse_mode: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_action_step() {
+        let mut executor = MockScriptExecutor::new();
+        executor
+            .expect_send_chat_action()
+            .with(eq(ChatAction::RecordVoice))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        run_step(&Step::ChatAction(ChatAction::RecordVoice), &executor, true)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delay_step_is_within_range() {
+        let mut executor = MockScriptExecutor::new();
+        executor
+            .expect_sleep()
+            .withf(|d| (Duration::from_secs(1)..=Duration::from_secs(3)).contains(d))
+            .times(10)
+            .return_const(());
+
+        let step = Step::Delay {
+            min: Duration::from_secs(1),
+            max: Duration::from_secs(3),
+        };
+        for _ in 0..10 {
+            run_step(&step, &executor, true).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_media_step() {
+        let mut executor = MockScriptExecutor::new();
+        executor
+            .expect_send_media()
+            .withf(|m, is_first| m.name == "voice" && !is_first)
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+
+        run_step(
+            &Step::Media(media("voice", MediaType::Voice)),
+            &executor,
+            false,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_script_runs_steps_in_order() {
+        let mut executor = MockScriptExecutor::new();
+        let mut seq = Sequence::new();
+        executor
+            .expect_send_chat_action()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(()));
+        executor
+            .expect_sleep()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_const(());
+        executor
+            .expect_send_media()
+            .withf(|m, is_first| m.name == "voice" && *is_first)
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(vec![MessageId(1)]));
+        executor
+            .expect_send_media()
+            .withf(|m, is_first| m.name == "text" && !is_first)
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(vec![MessageId(2)]));
+
+        let steps = [
+            Step::ChatAction(ChatAction::RecordVoice),
+            Step::Delay {
+                min: Duration::from_millis(1),
+                max: Duration::from_millis(3),
+            },
+            Step::Media(media("voice", MediaType::Voice)),
+            Step::Media(media("text", MediaType::PlainText)),
+        ];
+        assert_eq!(
+            run_script(&steps, &executor, &CancellationToken::new())
+                .await
+                .unwrap(),
+            vec![MessageId(1), MessageId(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_stops_script() {
+        let mut executor = MockScriptExecutor::new();
+        executor
+            .expect_send_chat_action()
+            .times(1)
+            .returning(|_| Err(anyhow::anyhow!("chat not found")));
+        executor.expect_send_media().never();
+
+        let steps = [
+            Step::ChatAction(ChatAction::Typing),
+            Step::Media(media("text", MediaType::PlainText)),
+        ];
+        assert!(run_script(&steps, &executor, &CancellationToken::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_script_does_nothing() {
+        let mut executor = MockScriptExecutor::new();
+        executor.expect_send_chat_action().never();
+        executor.expect_send_media().never();
+
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        let steps = [
+            Step::ChatAction(ChatAction::Typing),
+            Step::Media(media("text", MediaType::PlainText)),
+        ];
+        assert!(run_script(&steps, &executor, &shutdown).await.is_err());
+    }
+
+    #[test]
+    fn test_step_conversion() {
+        let mut chat_action = row(ScriptStepKind::ChatAction);
+        chat_action.chat_action = Some("record_voice".to_string());
+        assert!(matches!(
+            Step::try_from(chat_action),
+            Ok(Step::ChatAction(ChatAction::RecordVoice))
+        ));
+
+        let mut delay = row(ScriptStepKind::Delay);
+        delay.min_delay_ms = Some(1000);
+        delay.max_delay_ms = Some(3000);
+        assert!(matches!(Step::try_from(delay), Ok(Step::Delay { .. })));
+
+        let mut media = row(ScriptStepKind::Media);
+        media.media_name = Some("voice".to_string());
+        media.media_type = Some(MediaType::Voice);
+        media.media_parse_mode = Some(TextParseMode::None);
+        assert!(matches!(Step::try_from(media), Ok(Step::Media(_))));
+    }
+
+    #[test]
+    fn test_malformed_steps_are_rejected() {
+        let mut chat_action = row(ScriptStepKind::ChatAction);
+        chat_action.chat_action = Some("dancing".to_string());
+        assert!(Step::try_from(chat_action).is_err());
+
+        let mut delay = row(ScriptStepKind::Delay);
+        delay.min_delay_ms = Some(3000);
+        delay.max_delay_ms = Some(1000);
+        assert!(Step::try_from(delay).is_err());
+
+        let mut nested = row(ScriptStepKind::Media);
+        nested.media_name = Some("script".to_string());
+        nested.media_type = Some(MediaType::Script);
+        nested.media_parse_mode = Some(TextParseMode::None);
+        assert!(Step::try_from(nested).is_err());
+
+        assert!(Step::try_from(row(ScriptStepKind::Media)).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::Duration;
+use teloxide::prelude::*;
+
+use crate::bot::cache::{chat_settings_by_id, topic_settings_by_ids};
+use crate::database::repository::AsyncRepository;
+use crate::database::types::ChatSettings;
+
+/// A chat or one of its forum topics which has a cooldown of its own.
+pub type CooldownKey = (ChatId, Option<i32>);
+
+/// Settings in effect for a chat or a topic with the inherited ones resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EffectiveSettings {
+    pub text_trigger_enabled: bool,
+    pub duplicate_detection_enabled: bool,
+    /// Looking for copy-pasted texts is opt-in.
+    pub text_duplicate_detection_enabled: bool,
+    pub cooldown: Duration,
+    pub cooldown_key: CooldownKey,
+}
+
+/// Resolves settings of the topic, falling back to the ones of the chat and then to the defaults.
+pub async fn effective_settings(
+    repository: &mut AsyncRepository,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    default_cooldown: Duration,
+) -> anyhow::Result<EffectiveSettings> {
+    let chat = chat_settings_by_id(repository, chat_id.0).await?;
+    let topic = match thread_id {
+        Some(thread_id) => topic_settings_by_ids(repository, chat_id.0, thread_id).await?,
+        None => None,
+    };
+
+    Ok(resolve(chat, topic, chat_id, thread_id, default_cooldown))
+}
+
+fn resolve(
+    chat: Option<ChatSettings>,
+    topic: Option<ChatSettings>,
+    chat_id: ChatId,
+    thread_id: Option<i32>,
+    default_cooldown: Duration,
+) -> EffectiveSettings {
+    // unconfigured topics share the cooldown of the chat
+    let cooldown_key = (chat_id, thread_id.filter(|_| topic.is_some()));
+    let settings = topic.unwrap_or_default().or(chat.unwrap_or_default());
+
+    EffectiveSettings {
+        text_trigger_enabled: settings.text_trigger_enabled.unwrap_or(true),
+        duplicate_detection_enabled: settings.duplicate_detection_enabled.unwrap_or(true),
+        text_duplicate_detection_enabled: settings
+            .text_duplicate_detection_enabled
+            .unwrap_or(false),
+        cooldown: settings
+            .cooldown_secs
+            .map_or(default_cooldown, |x| Duration::seconds(x.into())),
+        cooldown_key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use teloxide::types::ChatId;
+
+    use crate::database::types::ChatSettings;
+
+    use super::{resolve, EffectiveSettings};
+
+    const CHAT: ChatId = ChatId(-100);
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(
+            resolve(None, None, CHAT, Some(7), Duration::seconds(60)),
+            EffectiveSettings {
+                text_trigger_enabled: true,
+                duplicate_detection_enabled: true,
+                text_duplicate_detection_enabled: false,
+                cooldown: Duration::seconds(60),
+                cooldown_key: (CHAT, None),
+            }
+        );
+    }
+
+    #[test]
+    fn test_topic_overrides_chat() {
+        let chat = ChatSettings {
+            text_trigger_enabled: Some(false),
+            duplicate_detection_enabled: Some(false),
+            cooldown_secs: Some(30),
+            text_duplicate_detection_enabled: Some(true),
+        };
+        let topic = ChatSettings {
+            text_trigger_enabled: Some(true),
+            cooldown_secs: Some(0),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resolve(
+                Some(chat),
+                Some(topic),
+                CHAT,
+                Some(7),
+                Duration::seconds(60)
+            ),
+            EffectiveSettings {
+                text_trigger_enabled: true,
+                duplicate_detection_enabled: false,
+                text_duplicate_detection_enabled: true,
+                cooldown: Duration::zero(),
+                cooldown_key: (CHAT, Some(7)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_chat_settings_without_topic() {
+        let chat = ChatSettings {
+            cooldown_secs: Some(30),
+            ..Default::default()
+        };
+
+        let settings = resolve(Some(chat), None, CHAT, Some(7), Duration::seconds(60));
+        assert_eq!(settings.cooldown, Duration::seconds(30));
+        assert_eq!(settings.cooldown_key, (CHAT, None));
+    }
+}
@@ -0,0 +1,27 @@
+use serde_json::{json, Value};
+use teloxide::types::Message;
+
+/// Builds a text message posted to a supergroup by a user,
+/// fields of `extra` are merged into it, e.g. to make it a forward.
+pub fn supergroup_message(text: &str, extra: Value) -> Message {
+    let mut message = json!({
+        "message_id": 42,
+        "date": 1695000000,
+        "chat": {"id": -1001847508954_i64, "title": "chat", "type": "supergroup"},
+        "from": {"id": 1253681278, "is_bot": false, "first_name": "Krusty"},
+        "text": text
+    });
+    merge(&mut message, extra);
+    serde_json::from_value(message).unwrap()
+}
+
+fn merge(base: &mut Value, extra: Value) {
+    match (base, extra) {
+        (Value::Object(base), Value::Object(extra)) => {
+            for (key, value) in extra {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, extra) => *base = extra,
+    }
+}
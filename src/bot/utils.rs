@@ -1,119 +1,611 @@
+use anyhow::anyhow;
 use bytes::Bytes;
 use chrono::{prelude::*, Duration};
 use rand::seq::SliceRandom;
 use std::cmp::Ordering;
-use teloxide::types::MessageId;
-use teloxide::{prelude::*, types::InputFile, Bot};
+use teloxide::requests::HasPayload;
+use teloxide::types::{
+    InputMedia, InputMediaDocument, InputMediaPhoto, InputMediaVideo, MessageId, MessageKind,
+    ParseMode,
+};
+use teloxide::{prelude::*, types::InputFile, ApiError, Bot, RequestError};
 
+use crate::database::payload::MediaPayload;
 use crate::database::repository::AsyncRepository;
-use crate::database::types::{MediaInfo, MediaType};
+use crate::database::types::{
+    MediaGroupItem, MediaInfo, MediaMetadata, MediaType, ReplyOptions, TextParseMode,
+};
+use crate::formatting::{telegram_parse_mode, validate};
+use crate::storage::MediaStores;
 
-use super::cache::media_data_by_name;
+use super::cache::{
+    media_data_by_name, media_file_id_by_name, media_group_items_by_name, media_metadata_by_name,
+    media_payload_by_name, set_media_file_id,
+};
+use super::script::send_script;
 
 macro_rules! send_with_caption {
-    ($request:expr, $caption:expr, $message_id:expr) => {{
-        let r = $request.caption($caption.unwrap_or_default());
-        send!(r, $message_id);
+    ($request:expr, $caption:expr, $delivery:expr) => {{
+        let (text, parse_mode) = caption_parts($caption);
+        let r = $request.caption(text.unwrap_or_default());
+        let r = match parse_mode {
+            Some(parse_mode) => r.parse_mode(parse_mode),
+            None => r,
+        };
+        send!(r, $delivery)
     }};
 }
 
 macro_rules! send {
-    ($request:expr, $message_id:expr) => {{
-        let r = $request.disable_notification(true);
-        if $message_id.is_some() {
-            r.reply_to_message_id($message_id.unwrap()).await?;
-        } else {
-            r.send().await?;
+    ($request:expr, $delivery:expr) => {
+        send!($request, $delivery, |x| x)
+    };
+    ($request:expr, $delivery:expr, $reply_to:expr) => {{
+        let delivery: Delivery = $delivery;
+        let mut r = $request.disable_notification(!delivery.notify);
+        r.payload_mut().message_thread_id = delivery.thread_id;
+        match delivery.reply_to.map($reply_to) {
+            Some(message_id) => r.reply_to_message_id(message_id).await,
+            None => r.send().await,
         }
     }};
 }
 
+#[derive(Clone, Debug)]
+pub struct Caption {
+    pub text: String,
+    pub parse_mode: TextParseMode,
+}
+
+// Everything besides the file itself which is needed to send it.
+struct FileExtras<'a> {
+    payload: Option<&'a MediaPayload>,
+    metadata: &'a MediaMetadata,
+    // Telegram ignores thumbnails unless the file itself is uploaded
+    with_thumbnail: bool,
+}
+
+impl FileExtras<'_> {
+    fn thumbnail(&self) -> Option<InputFile> {
+        self.metadata
+            .thumbnail
+            .clone()
+            .filter(|_| self.with_thumbnail)
+            .map(|x| InputFile::memory(Bytes::from(x)).file_name("thumbnail.jpg"))
+    }
+}
+
+/// Where and how a media is sent within the chat.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Delivery {
+    /// Message to quote, the media is just sent to the chat if there is none.
+    pub reply_to: Option<MessageId>,
+    /// Forum topic to send to, the General one is used if there is none.
+    pub thread_id: Option<i32>,
+    pub notify: bool,
+}
+
+impl Delivery {
+    /// Responds to the message in its topic, quoting it unless the options say otherwise.
+    pub fn response_to(message: &Message, options: ReplyOptions) -> Self {
+        Delivery {
+            reply_to: options.reply.unwrap_or(true).then_some(message.id),
+            thread_id: topic_id(message),
+            notify: options.notify.unwrap_or(false),
+        }
+    }
+
+    /// The same delivery without quoting, e.g. for follow-up messages.
+    pub fn without_reply(self) -> Self {
+        Delivery {
+            reply_to: None,
+            ..self
+        }
+    }
+}
+
+/// Forum topic of the message, if any.
+// Replies in ordinary supergroups have thread ids as well,
+// but only forum topics accept them for sending.
+pub fn topic_id(message: &Message) -> Option<i32> {
+    match &message.kind {
+        MessageKind::Common(common) if common.is_topic_message => message.thread_id,
+        _ => None,
+    }
+}
+
+struct AlbumFile {
+    file: InputFile,
+    thumbnail: Option<InputFile>,
+    metadata: MediaMetadata,
+}
+
+/// Sends the plain text, e.g. a reply to a command. Returns id of the sent message.
+pub async fn send_text(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: String,
+    delivery: Delivery,
+) -> anyhow::Result<MessageId> {
+    Ok(send!(bot.send_message(chat_id, text), delivery)?.id)
+}
+
+/// Sends the media with the given caption or, if there is none, with the default one of the media.
+/// Returns ids of the sent messages.
 pub async fn send_media(
     media: &MediaInfo,
     repository: &mut AsyncRepository,
+    stores: &MediaStores,
     bot: Bot,
     chat_id: ChatId,
-    message_id: Option<MessageId>,
-    caption: Option<String>,
-) -> anyhow::Result<()> {
-    let data = media_data_by_name(repository, &media.name).await?;
+    delivery: Delivery,
+    caption: Option<Caption>,
+) -> anyhow::Result<Vec<MessageId>> {
+    let caption = caption.or_else(|| {
+        media.caption.clone().map(|text| Caption {
+            text,
+            parse_mode: media.parse_mode.clone(),
+        })
+    });
 
     match media.type_ {
-        MediaType::Voice => {
-            send_with_caption!(
-                bot.send_voice(
-                    chat_id,
-                    InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
+        MediaType::PlainText => {
+            let metadata = media_metadata_by_name(repository, &media.name).await?;
+            let data = media_data_by_name(repository, stores, &media.name).await?;
+            let mut r = bot.send_message(
+                chat_id,
+                String::from_utf8(data).expect("Failed to convert from bytes"),
+            );
+            r.payload_mut().protect_content = flag(metadata.protect_content);
+            let r = match telegram_parse_mode(&media.parse_mode) {
+                Some(parse_mode) => r.parse_mode(parse_mode),
+                None => r,
+            };
+            return Ok(vec![send!(r, delivery)?.id]);
+        }
+        MediaType::Unknown => {
+            log::error!("Unknown media file type, check DB");
+            return Ok(vec![]);
+        }
+        MediaType::Album => {
+            return send_album(media, repository, stores, bot, chat_id, delivery, caption).await
+        }
+        MediaType::Script => {
+            return send_script(media, repository, stores, bot, chat_id, delivery, caption).await
+        }
+        MediaType::Location
+        | MediaType::Venue
+        | MediaType::Dice
+        | MediaType::Poll
+        | MediaType::Contact => {
+            let payload = media_payload(repository, media)
+                .await?
+                .ok_or_else(|| anyhow!("Media '{}' has no payload", media.name))?;
+            let metadata = media_metadata_by_name(repository, &media.name).await?;
+            let message =
+                send_payload(payload, metadata.protect_content, bot, chat_id, delivery).await?;
+            return Ok(vec![message.id]);
+        }
+        _ => {}
+    }
+
+    let payload = media_payload(repository, media).await?;
+    let metadata = media_metadata_by_name(repository, &media.name).await?;
+    let mut extras = FileExtras {
+        payload: payload.as_ref(),
+        metadata: &metadata,
+        with_thumbnail: false,
+    };
+
+    if let Some(file_id) = media_file_id_by_name(repository, &media.name).await? {
+        match send_file(
+            &media.type_,
+            &extras,
+            InputFile::file_id(file_id),
+            bot.clone(),
+            chat_id,
+            delivery,
+            caption.clone(),
+        )
+        .await
+        {
+            Ok(message) => return Ok(vec![message.id]),
+            Err(e) if is_stale_file_id_error(&e) => {
+                log::warn!(
+                    "Telegram rejected file id of media '{}', uploading it again: '{e}'",
+                    media.name
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    extras.with_thumbnail = true;
+    let data = media_data_by_name(repository, stores, &media.name).await?;
+    let message = send_file(
+        &media.type_,
+        &extras,
+        InputFile::memory(Bytes::from(data)).file_name(media.name.clone()),
+        bot,
+        chat_id,
+        delivery,
+        caption,
+    )
+    .await?;
+
+    if let Err(e) = set_media_file_id(repository, &media.name, uploaded_file_id(&message)).await {
+        log::error!("Failed to save file id of media '{}': '{e}'", media.name);
+    }
+
+    Ok(vec![message.id])
+}
+
+async fn media_payload(
+    repository: &mut AsyncRepository,
+    media: &MediaInfo,
+) -> anyhow::Result<Option<MediaPayload>> {
+    let payload = media_payload_by_name(repository, &media.name).await?;
+    MediaPayload::parse(&media.type_, payload.as_ref())
+        .map_err(|e| anyhow!("Media '{}' is malformed: {e}", media.name))
+}
+
+async fn send_payload(
+    payload: MediaPayload,
+    protect_content: bool,
+    bot: Bot,
+    chat_id: ChatId,
+    delivery: Delivery,
+) -> Result<Message, RequestError> {
+    let protect_content = flag(protect_content);
+    match payload {
+        MediaPayload::Location(x) => {
+            let mut r = bot.send_location(chat_id, x.latitude, x.longitude);
+            r.payload_mut().protect_content = protect_content;
+            send!(r, delivery)
+        }
+        MediaPayload::Venue(x) => {
+            let mut r = bot.send_venue(chat_id, x.latitude, x.longitude, x.title, x.address);
+            r.payload_mut().foursquare_id = x.foursquare_id;
+            r.payload_mut().protect_content = protect_content;
+            send!(r, delivery)
+        }
+        MediaPayload::Dice(x) => {
+            let mut r = bot.send_dice(chat_id).emoji(x.emoji);
+            r.payload_mut().protect_content = protect_content;
+            send!(r, delivery)
+        }
+        MediaPayload::Poll(x) => {
+            let mut r = bot
+                .send_poll(chat_id, x.question, x.options)
+                .is_anonymous(x.is_anonymous)
+                .allows_multiple_answers(x.allows_multiple_answers);
+            r.payload_mut().protect_content = protect_content;
+            send!(r, delivery)
+        }
+        MediaPayload::Contact(x) => {
+            let mut r = bot.send_contact(chat_id, x.phone_number, x.first_name);
+            r.payload_mut().last_name = x.last_name;
+            r.payload_mut().protect_content = protect_content;
+            send!(r, delivery)
+        }
+        MediaPayload::Audio(_) => unreachable!("Audio is sent as a file"),
+    }
+}
+
+async fn send_album(
+    media: &MediaInfo,
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    bot: Bot,
+    chat_id: ChatId,
+    delivery: Delivery,
+    caption: Option<Caption>,
+) -> anyhow::Result<Vec<MessageId>> {
+    let items = media_group_items_by_name(repository, &media.name).await?;
+    validate_album(&items).map_err(|e| anyhow!("Album '{}' is malformed: {e}", media.name))?;
+    let album_metadata = media_metadata_by_name(repository, &media.name).await?;
+
+    let mut file_ids = Vec::with_capacity(items.len());
+    let mut metadata = Vec::with_capacity(items.len());
+    for item in &items {
+        file_ids.push(media_file_id_by_name(repository, &item.name).await?);
+        metadata.push(media_metadata_by_name(repository, &item.name).await?);
+    }
+
+    let mut force_upload = false;
+    loop {
+        let mut files = Vec::with_capacity(items.len());
+        for ((item, file_id), metadata) in items.iter().zip(&file_ids).zip(&metadata) {
+            let mut extras = FileExtras {
+                payload: None,
+                metadata,
+                with_thumbnail: false,
+            };
+            let file = match file_id {
+                Some(file_id) if !force_upload => InputFile::file_id(file_id),
+                _ => {
+                    extras.with_thumbnail = true;
+                    let data = media_data_by_name(repository, stores, &item.name).await?;
+                    InputFile::memory(Bytes::from(data)).file_name(item.name.clone())
+                }
+            };
+            files.push(AlbumFile {
+                file,
+                thumbnail: extras.thumbnail(),
+                metadata: metadata.clone(),
+            });
+        }
+
+        let mut request = bot.send_media_group(
+            chat_id,
+            album_media(&items, files, caption.clone(), &media.parse_mode),
+        );
+        request.payload_mut().protect_content = flag(album_metadata.protect_content);
+        match send!(request, delivery) {
+            Ok(messages) => {
+                for ((item, file_id), message) in items.iter().zip(&file_ids).zip(&messages) {
+                    if file_id.is_some() && !force_upload {
+                        continue;
+                    }
+                    if let Err(e) =
+                        set_media_file_id(repository, &item.name, uploaded_file_id(message)).await
+                    {
+                        log::error!("Failed to save file id of media '{}': '{e}'", item.name);
+                    }
+                }
+                return Ok(messages.iter().map(|x| x.id).collect());
+            }
+            Err(e) if !force_upload && file_ids.iter().any(Option::is_some) => {
+                if !is_stale_file_id_error(&e) {
+                    return Err(e.into());
+                }
+                log::warn!(
+                    "Telegram rejected file ids of album '{}', uploading it again: '{e}'",
+                    media.name
+                );
+                force_upload = true;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn validate_album(items: &[MediaGroupItem]) -> anyhow::Result<()> {
+    if !(2..=10).contains(&items.len()) {
+        return Err(anyhow!(
+            "album must have from 2 to 10 items, got {}",
+            items.len()
+        ));
+    }
+
+    if let Some(item) = items.iter().find(|x| {
+        !matches!(
+            x.type_,
+            MediaType::Picture | MediaType::Video | MediaType::Document
+        )
+    }) {
+        return Err(anyhow!(
+            "item '{}' of type '{:?}' can't be a part of an album",
+            item.name,
+            item.type_
+        ));
+    }
+
+    let documents = items
+        .iter()
+        .filter(|x| x.type_ == MediaType::Document)
+        .count();
+    if documents != 0 && documents != items.len() {
+        return Err(anyhow!("documents can't be mixed with pictures and videos"));
+    }
+
+    Ok(())
+}
+
+// Telegram shows the caption of the first item as the album caption,
+// so the one passed by a caller takes its place.
+// Item captions are formatted with the parse mode of the album,
+// the ones with broken markup are sent as plain text.
+fn album_media(
+    items: &[MediaGroupItem],
+    files: Vec<AlbumFile>,
+    caption: Option<Caption>,
+    parse_mode: &TextParseMode,
+) -> Vec<InputMedia> {
+    items
+        .iter()
+        .zip(files)
+        .enumerate()
+        .map(
+            |(
+                i,
+                (
+                    item,
+                    AlbumFile {
+                        file,
+                        thumbnail,
+                        metadata,
+                    },
                 ),
-                caption,
-                message_id
-            )
+            )| {
+                let item_caption = item.caption.clone().map(|text| {
+                    let parse_mode = match validate(&text, parse_mode) {
+                        Ok(()) => parse_mode.clone(),
+                        Err(e) => {
+                            log::warn!("Caption of album item '{}' is malformed: {e}", item.name);
+                            TextParseMode::None
+                        }
+                    };
+                    Caption { text, parse_mode }
+                });
+                let (caption, parse_mode) = caption_parts(match i {
+                    0 => caption.clone().or(item_caption),
+                    _ => item_caption,
+                });
+
+                match item.type_ {
+                    MediaType::Picture => InputMedia::Photo(InputMediaPhoto {
+                        caption,
+                        parse_mode,
+                        has_spoiler: metadata.has_spoiler,
+                        ..InputMediaPhoto::new(file)
+                    }),
+                    MediaType::Video => InputMedia::Video(InputMediaVideo {
+                        caption,
+                        parse_mode,
+                        thumb: thumbnail,
+                        width: metadata.width.and_then(|x| x.try_into().ok()),
+                        height: metadata.height.and_then(|x| x.try_into().ok()),
+                        duration: metadata.duration_secs.and_then(|x| x.try_into().ok()),
+                        has_spoiler: metadata.has_spoiler,
+                        ..InputMediaVideo::new(file)
+                    }),
+                    _ => InputMedia::Document(InputMediaDocument {
+                        caption,
+                        parse_mode,
+                        thumb: thumbnail,
+                        ..InputMediaDocument::new(file)
+                    }),
+                }
+            },
+        )
+        .collect()
+}
+
+fn caption_parts(caption: Option<Caption>) -> (Option<String>, Option<ParseMode>) {
+    match caption {
+        Some(caption) => (Some(caption.text), telegram_parse_mode(&caption.parse_mode)),
+        None => (None, None),
+    }
+}
+
+async fn send_file(
+    type_: &MediaType,
+    extras: &FileExtras<'_>,
+    file: InputFile,
+    bot: Bot,
+    chat_id: ChatId,
+    delivery: Delivery,
+    caption: Option<Caption>,
+) -> Result<Message, RequestError> {
+    let m = extras.metadata;
+    match type_ {
+        MediaType::Voice => {
+            // SendVoice of teloxide-core 0.9.1 lacks protect_content, unlike the bot api,
+            // so ingestion rejects the flag for voices until an upgrade
+            let mut r = bot.send_voice(chat_id, file);
+            r.payload_mut().duration = number(m.duration_secs);
+            send_with_caption!(r, caption, delivery)
         }
         MediaType::Picture => {
-            send_with_caption!(
-                bot.send_photo(
-                    chat_id,
-                    InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-                ),
-                caption,
-                message_id
-            )
+            let mut r = bot.send_photo(chat_id, file);
+            let p = r.payload_mut();
+            p.has_spoiler = flag(m.has_spoiler);
+            p.protect_content = flag(m.protect_content);
+            send_with_caption!(r, caption, delivery)
         }
         MediaType::Video => {
-            send_with_caption!(
-                bot.send_video(
-                    chat_id,
-                    InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-                ),
-                caption,
-                message_id
-            )
+            let mut r = bot.send_video(chat_id, file);
+            let p = r.payload_mut();
+            p.duration = number(m.duration_secs);
+            p.width = number(m.width);
+            p.height = number(m.height);
+            p.thumb = extras.thumbnail();
+            p.has_spoiler = flag(m.has_spoiler);
+            p.protect_content = flag(m.protect_content);
+            send_with_caption!(r, caption, delivery)
         }
         MediaType::Animation => {
-            send_with_caption!(
-                bot.send_animation(
-                    chat_id,
-                    InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-                ),
-                caption,
-                message_id
-            )
+            let mut r = bot.send_animation(chat_id, file);
+            let p = r.payload_mut();
+            p.duration = number(m.duration_secs);
+            p.width = number(m.width);
+            p.height = number(m.height);
+            p.thumb = extras.thumbnail();
+            p.has_spoiler = flag(m.has_spoiler);
+            p.protect_content = flag(m.protect_content);
+            send_with_caption!(r, caption, delivery)
         }
-        MediaType::PlainText => send!(
-            bot.send_message(
-                chat_id,
-                String::from_utf8(data).expect("Failed to convert from bytes"),
-            ),
-            message_id
-        ),
         MediaType::Document => {
-            send_with_caption!(
-                bot.send_document(
-                    chat_id,
-                    InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-                ),
-                caption,
-                message_id
-            )
+            let mut r = bot.send_document(chat_id, file);
+            let p = r.payload_mut();
+            p.thumb = extras.thumbnail();
+            p.protect_content = flag(m.protect_content);
+            send_with_caption!(r, caption, delivery)
+        }
+        MediaType::Audio => {
+            let mut r = bot.send_audio(chat_id, file);
+            let p = r.payload_mut();
+            if let Some(MediaPayload::Audio(audio)) = extras.payload {
+                p.title = audio.title.clone();
+                p.performer = audio.performer.clone();
+            }
+            p.duration = number(m.duration_secs);
+            p.thumb = extras.thumbnail();
+            p.protect_content = flag(m.protect_content);
+            send_with_caption!(r, caption, delivery)
+        }
+        MediaType::VideoNote => {
+            let mut r = bot.send_video_note(chat_id, file);
+            let p = r.payload_mut();
+            p.duration = number(m.duration_secs);
+            p.length = number(m.width);
+            p.thumb = extras.thumbnail();
+            p.protect_content = flag(m.protect_content);
+            send!(r, delivery)
+        }
+        MediaType::Sticker => {
+            let mut r = bot.send_sticker(chat_id, file);
+            r.payload_mut().protect_content = flag(m.protect_content);
+            send!(r, delivery, |x: MessageId| x.0)
+        }
+        MediaType::PlainText
+        | MediaType::Unknown
+        | MediaType::Album
+        | MediaType::Script
+        | MediaType::Location
+        | MediaType::Venue
+        | MediaType::Dice
+        | MediaType::Poll
+        | MediaType::Contact => {
+            unreachable!("Media of type '{type_:?}' is not a file")
         }
-        MediaType::VideoNote => send!(
-            bot.send_video_note(
-                chat_id,
-                InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-            ),
-            message_id
-        ),
-        MediaType::Sticker => send!(
-            bot.send_sticker(
-                chat_id,
-                InputFile::memory(Bytes::from(data)).file_name(media.name.clone())
-            ),
-            message_id.map(|x| x.0)
-        ),
-        MediaType::Unknown => log::error!("Unknown media file type, check DB"),
     }
+}
 
-    Ok(())
+// Telegram treats absent flags as unset ones.
+fn flag(x: bool) -> Option<bool> {
+    x.then_some(true)
+}
+
+fn number(x: Option<i32>) -> Option<u32> {
+    x.and_then(|x| x.try_into().ok())
+}
+
+fn uploaded_file_id(message: &Message) -> Option<String> {
+    message
+        .voice()
+        .map(|x| &x.file)
+        .or_else(|| message.photo().and_then(|x| x.last()).map(|x| &x.file))
+        .or_else(|| message.video().map(|x| &x.file))
+        .or_else(|| message.animation().map(|x| &x.file))
+        .or_else(|| message.document().map(|x| &x.file))
+        .or_else(|| message.video_note().map(|x| &x.file))
+        .or_else(|| message.sticker().map(|x| &x.file))
+        .or_else(|| message.audio().map(|x| &x.file))
+        .map(|x| x.id.clone())
+}
+
+fn is_stale_file_id_error(e: &RequestError) -> bool {
+    match e {
+        RequestError::Api(ApiError::WrongFileId)
+        | RequestError::Api(ApiError::WrongFileIdOrUrl)
+        | RequestError::Api(ApiError::FileIdInvalid) => true,
+        RequestError::Api(ApiError::Unknown(description)) => {
+            description.contains("file identifier") || description.contains("file id")
+        }
+        _ => false,
+    }
 }
 
 pub fn choose_random_media_info(media_infos: &[MediaInfo]) -> Option<&MediaInfo> {
@@ -123,3 +615,226 @@ pub fn choose_random_media_info(media_infos: &[MediaInfo]) -> Option<&MediaInfo>
 pub fn is_time_passed(datetime: &DateTime<Utc>, duration: &Duration) -> bool {
     Utc::now().signed_duration_since(*datetime).cmp(duration) == Ordering::Greater
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use teloxide::types::{InputFile, InputMedia, Message, MessageId};
+
+    use teloxide::types::ParseMode;
+
+    use crate::bot::test_utils::supergroup_message;
+    use crate::database::types::{MediaGroupItem, MediaType, ReplyOptions, TextParseMode};
+
+    use crate::database::types::MediaMetadata;
+
+    use super::{album_media, validate_album, AlbumFile, Caption, Delivery};
+
+    fn item(type_: MediaType, caption: Option<&str>) -> MediaGroupItem {
+        MediaGroupItem {
+            name: format!("{type_:?}"),
+            type_,
+            caption: caption.map(str::to_string),
+        }
+    }
+
+    fn file(file_id: &str, metadata: MediaMetadata) -> AlbumFile {
+        AlbumFile {
+            file: InputFile::file_id(file_id),
+            thumbnail: None,
+            metadata,
+        }
+    }
+
+    fn caption(media: &InputMedia) -> (Option<&str>, Option<ParseMode>) {
+        match media {
+            InputMedia::Photo(x) => (x.caption.as_deref(), x.parse_mode),
+            InputMedia::Video(x) => (x.caption.as_deref(), x.parse_mode),
+            InputMedia::Document(x) => (x.caption.as_deref(), x.parse_mode),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_valid_album() {
+        assert!(
+            validate_album(&[item(MediaType::Picture, None), item(MediaType::Video, None)]).is_ok()
+        );
+        assert!(validate_album(&[
+            item(MediaType::Document, None),
+            item(MediaType::Document, None)
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn test_album_size_is_limited() {
+        assert!(validate_album(&[item(MediaType::Picture, None)]).is_err());
+        assert!(validate_album(&vec![item(MediaType::Picture, None); 11]).is_err());
+    }
+
+    #[test]
+    fn test_album_item_types_are_limited() {
+        assert!(
+            validate_album(&[item(MediaType::Picture, None), item(MediaType::Voice, None)])
+                .is_err()
+        );
+        assert!(validate_album(&[
+            item(MediaType::Picture, None),
+            item(MediaType::Document, None)
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_album_captions() {
+        let items = [
+            item(MediaType::Picture, Some("*first*")),
+            item(MediaType::Video, Some("*second*")),
+            item(MediaType::Video, None),
+            item(MediaType::Video, Some("*broken")),
+        ];
+        let files = || {
+            ["1", "2", "3", "4"]
+                .map(|x| file(x, MediaMetadata::default()))
+                .into()
+        };
+
+        let media = album_media(&items, files(), None, &TextParseMode::MarkdownV2);
+        assert_eq!(
+            caption(&media[0]),
+            (Some("*first*"), Some(ParseMode::MarkdownV2))
+        );
+        assert_eq!(
+            caption(&media[1]),
+            (Some("*second*"), Some(ParseMode::MarkdownV2))
+        );
+        assert_eq!(caption(&media[2]), (None, None));
+        assert_eq!(caption(&media[3]), (Some("*broken"), None));
+
+        let media = album_media(
+            &items,
+            files(),
+            Some(Caption {
+                text: "caption".to_string(),
+                parse_mode: TextParseMode::None,
+            }),
+            &TextParseMode::MarkdownV2,
+        );
+        assert_eq!(caption(&media[0]), (Some("caption"), None));
+        assert_eq!(
+            caption(&media[1]),
+            (Some("*second*"), Some(ParseMode::MarkdownV2))
+        );
+    }
+
+    #[test]
+    fn test_album_metadata() {
+        let items = [item(MediaType::Picture, None), item(MediaType::Video, None)];
+        let files = vec![
+            file(
+                "1",
+                MediaMetadata {
+                    has_spoiler: true,
+                    ..Default::default()
+                },
+            ),
+            file(
+                "2",
+                MediaMetadata {
+                    duration_secs: Some(12),
+                    width: Some(1280),
+                    height: Some(720),
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        let media = album_media(&items, files, None, &TextParseMode::None);
+        match &media[..] {
+            [InputMedia::Photo(photo), InputMedia::Video(video)] => {
+                assert!(photo.has_spoiler);
+                assert!(!video.has_spoiler);
+                assert_eq!(video.duration, Some(12));
+                assert_eq!((video.width, video.height), (Some(1280), Some(720)));
+            }
+            _ => panic!("photo and video are expected"),
+        }
+    }
+
+    fn message(is_forum: bool, thread_id: Option<i32>) -> Message {
+        supergroup_message(
+            "beer",
+            json!({
+                "message_thread_id": thread_id,
+                "is_topic_message": is_forum && thread_id.is_some(),
+                "chat": {"is_forum": is_forum}
+            }),
+        )
+    }
+
+    #[test]
+    fn test_delivery_defaults() {
+        assert_eq!(
+            Delivery::response_to(&message(false, None), ReplyOptions::default()),
+            Delivery {
+                reply_to: Some(MessageId(42)),
+                thread_id: None,
+                notify: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_delivery_options() {
+        let options = ReplyOptions {
+            reply: Some(false),
+            notify: Some(true),
+        };
+        assert_eq!(
+            Delivery::response_to(&message(false, None), options),
+            Delivery {
+                reply_to: None,
+                thread_id: None,
+                notify: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_delivery_thread() {
+        // plain sends in forums still go to the topic of the trigger message
+        let options = ReplyOptions {
+            reply: Some(false),
+            notify: None,
+        };
+        assert_eq!(
+            Delivery::response_to(&message(true, Some(7)), options).thread_id,
+            Some(7)
+        );
+        // threads of replies in ordinary supergroups are not topics
+        assert_eq!(
+            Delivery::response_to(&message(false, Some(7)), options).thread_id,
+            None
+        );
+    }
+
+    #[test]
+    fn test_reply_options_precedence() {
+        let tag = ReplyOptions {
+            reply: Some(false),
+            notify: None,
+        };
+        let feature = ReplyOptions {
+            reply: Some(true),
+            notify: Some(true),
+        };
+        assert_eq!(
+            tag.or(feature),
+            ReplyOptions {
+                reply: Some(false),
+                notify: Some(true),
+            }
+        );
+    }
+}
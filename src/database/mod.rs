@@ -1,2 +1,4 @@
+pub mod notifications;
+pub mod payload;
 pub mod repository;
 pub mod types;
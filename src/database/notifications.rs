@@ -0,0 +1,60 @@
+use futures::{stream, StreamExt};
+use tokio::sync::broadcast;
+use tokio_postgres::{AsyncMessage, NoTls};
+
+/// Channel the triggers of the tables with settings and media notify, see migrations.
+pub const TABLE_CHANGES_CHANNEL: &str = "table_changes";
+
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TableChange {
+    /// Name of the changed table.
+    Table(String),
+    /// Any table may have changed while the listener has been reconnecting.
+    Any,
+}
+
+/// Forwards changes of the tables to `changes`, reconnecting whenever the connection is lost.
+pub async fn listen_for_table_changes(db_url: String, changes: broadcast::Sender<TableChange>) {
+    let mut reconnecting = false;
+    loop {
+        match listen(&db_url, &changes, reconnecting).await {
+            Ok(()) => log::warn!("Connection listening for table changes has been closed"),
+            Err(e) => log::error!("Failed to listen for table changes: '{e}'"),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+        reconnecting = true;
+    }
+}
+
+async fn listen(
+    db_url: &str,
+    changes: &broadcast::Sender<TableChange>,
+    reconnecting: bool,
+) -> anyhow::Result<()> {
+    let (client, mut connection) = tokio_postgres::connect(db_url, NoTls).await?;
+
+    // the connection makes progress only while its messages are polled
+    let notifications = changes.clone();
+    let messages = tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            if let AsyncMessage::Notification(n) = message? {
+                // nobody is subscribed yet if the bot is still starting up
+                let _ = notifications.send(TableChange::Table(n.payload().to_string()));
+            }
+        }
+        Ok::<_, tokio_postgres::Error>(())
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {TABLE_CHANGES_CHANNEL}"))
+        .await?;
+    log::info!("Listening for table changes");
+    if reconnecting {
+        let _ = changes.send(TableChange::Any);
+    }
+
+    Ok(messages.await??)
+}
@@ -0,0 +1,253 @@
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use teloxide::types::DiceEmoji;
+
+use crate::database::types::MediaType;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AudioPayload {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LocationPayload {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VenuePayload {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub title: String,
+    pub address: String,
+    pub foursquare_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DicePayload {
+    /// One of 🎲, 🎯, 🏀, ⚽, 🎳 or 🎰.
+    pub emoji: DiceEmoji,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PollPayload {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default = "default_true")]
+    pub is_anonymous: bool,
+    #[serde(default)]
+    pub allows_multiple_answers: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ContactPayload {
+    pub phone_number: String,
+    pub first_name: String,
+    pub last_name: Option<String>,
+}
+
+/// Structured part of a media which Telegram needs besides (or instead of) a file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaPayload {
+    Audio(AudioPayload),
+    Location(LocationPayload),
+    Venue(VenuePayload),
+    Dice(DicePayload),
+    Poll(PollPayload),
+    Contact(ContactPayload),
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl MediaPayload {
+    /// Parses and validates the payload stored for a media of the given type.
+    /// Returns `None` for types which have no payload.
+    pub fn parse(
+        type_: &MediaType,
+        payload: Option<&serde_json::Value>,
+    ) -> anyhow::Result<Option<Self>> {
+        let payload = match (type_, payload) {
+            (MediaType::Audio, None) => return Ok(None),
+            (MediaType::Audio, Some(x)) => MediaPayload::Audio(from_value(x)?),
+            (MediaType::Location, Some(x)) => MediaPayload::Location(from_value(x)?),
+            (MediaType::Venue, Some(x)) => MediaPayload::Venue(from_value(x)?),
+            (MediaType::Dice, Some(x)) => MediaPayload::Dice(from_value(x)?),
+            (MediaType::Poll, Some(x)) => MediaPayload::Poll(from_value(x)?),
+            (MediaType::Contact, Some(x)) => MediaPayload::Contact(from_value(x)?),
+            (
+                MediaType::Location
+                | MediaType::Venue
+                | MediaType::Dice
+                | MediaType::Poll
+                | MediaType::Contact,
+                None,
+            ) => return Err(anyhow!("{type_:?} has no payload")),
+            (_, Some(_)) => return Err(anyhow!("{type_:?} can't have payload")),
+            (_, None) => return Ok(None),
+        };
+
+        payload.validate()?;
+        Ok(Some(payload))
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            MediaPayload::Audio(_) | MediaPayload::Dice(_) => Ok(()),
+            MediaPayload::Location(x) => validate_coordinates(x.latitude, x.longitude),
+            MediaPayload::Venue(x) => {
+                validate_coordinates(x.latitude, x.longitude)?;
+                validate_length("title", &x.title, 1, 256)?;
+                validate_length("address", &x.address, 1, 256)
+            }
+            MediaPayload::Poll(x) => {
+                validate_length("question", &x.question, 1, 300)?;
+                if !(2..=10).contains(&x.options.len()) {
+                    return Err(anyhow!(
+                        "poll must have from 2 to 10 options, got {}",
+                        x.options.len()
+                    ));
+                }
+                x.options
+                    .iter()
+                    .try_for_each(|option| validate_length("option", option, 1, 100))
+            }
+            MediaPayload::Contact(x) => {
+                validate_length("phone number", &x.phone_number, 1, 64)?;
+                validate_length("first name", &x.first_name, 1, 64)
+            }
+        }
+    }
+}
+
+fn from_value<T: DeserializeOwned>(value: &serde_json::Value) -> anyhow::Result<T> {
+    T::deserialize(value).map_err(|e| anyhow!("payload is malformed: {e}"))
+}
+
+fn validate_coordinates(latitude: f64, longitude: f64) -> anyhow::Result<()> {
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(anyhow!(
+            "coordinates ({latitude}, {longitude}) are out of range"
+        ));
+    }
+    Ok(())
+}
+
+fn validate_length(field: &str, value: &str, min: usize, max: usize) -> anyhow::Result<()> {
+    let length = value.chars().count();
+    if !(min..=max).contains(&length) {
+        return Err(anyhow!(
+            "{field} must have from {min} to {max} characters, got {length}"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use teloxide::types::DiceEmoji;
+
+    use crate::database::types::MediaType;
+
+    use super::{DicePayload, MediaPayload};
+
+    #[test]
+    fn test_valid_payloads() {
+        for (type_, payload) in [
+            (
+                MediaType::Audio,
+                json!({"title": "Song", "performer": "Band"}),
+            ),
+            (
+                MediaType::Location,
+                json!({"latitude": 50.45, "longitude": 30.52}),
+            ),
+            (
+                MediaType::Venue,
+                json!({"latitude": 50.45, "longitude": 30.52, "title": "Bar", "address": "Main st. 1"}),
+            ),
+            (MediaType::Dice, json!({"emoji": "🎯"})),
+            (
+                MediaType::Poll,
+                json!({"question": "Beer?", "options": ["Yes", "No"], "is_anonymous": false}),
+            ),
+            (
+                MediaType::Contact,
+                json!({"phone_number": "+380000000000", "first_name": "Krusty"}),
+            ),
+        ] {
+            assert!(
+                MediaPayload::parse(&type_, Some(&payload)).is_ok(),
+                "{payload} should be a valid {type_:?} payload"
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_payloads_are_rejected() {
+        for (type_, payload) in [
+            (MediaType::Audio, json!({"title": "Song", "year": 1999})),
+            (
+                MediaType::Location,
+                json!({"latitude": 91.0, "longitude": 30.52}),
+            ),
+            (MediaType::Location, json!({"latitude": "50.45"})),
+            (
+                MediaType::Venue,
+                json!({"latitude": 50.45, "longitude": 30.52, "title": "", "address": "Main st. 1"}),
+            ),
+            (MediaType::Dice, json!({"emoji": "🍺"})),
+            (
+                MediaType::Poll,
+                json!({"question": "Beer?", "options": ["Yes"]}),
+            ),
+            (
+                MediaType::Poll,
+                json!({"question": "Beer?", "options": ["Yes", ""]}),
+            ),
+            (MediaType::Contact, json!({"first_name": "Krusty"})),
+            (MediaType::Voice, json!({"title": "Song"})),
+        ] {
+            assert!(
+                MediaPayload::parse(&type_, Some(&payload)).is_err(),
+                "{payload} should be an invalid {type_:?} payload"
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_payload() {
+        assert!(MediaPayload::parse(&MediaType::Poll, None).is_err());
+        assert_eq!(MediaPayload::parse(&MediaType::Audio, None).unwrap(), None);
+        assert_eq!(MediaPayload::parse(&MediaType::Voice, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_poll_defaults() {
+        let payload = json!({"question": "Beer?", "options": ["Yes", "No"]});
+        match MediaPayload::parse(&MediaType::Poll, Some(&payload)).unwrap() {
+            Some(MediaPayload::Poll(poll)) => {
+                assert!(poll.is_anonymous);
+                assert!(!poll.allows_multiple_answers);
+            }
+            _ => panic!("poll payload is expected"),
+        }
+
+        assert_eq!(
+            MediaPayload::parse(&MediaType::Dice, Some(&json!({"emoji": "🎲"}))).unwrap(),
+            Some(MediaPayload::Dice(DicePayload {
+                emoji: DiceEmoji::Dice
+            }))
+        );
+    }
+}
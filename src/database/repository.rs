@@ -1,3 +1,4 @@
+use chrono::prelude::*;
 use deadpool::managed::Pool;
 use diesel::{insert_into, prelude::*};
 use diesel_async::{pooled_connection::AsyncDieselConnectionManager, RunQueryDsl};
@@ -5,10 +6,71 @@ use tracing_attributes::instrument;
 
 use crate::database::types;
 use crate::schema::forwarded_messages;
-use crate::schema::forwarded_messages::dsl::*;
 use crate::schema::media;
 use crate::schema::media::dsl::*;
 
+const UPSERT_ATTEMPTS: usize = 3;
+
+// Registers the row of known posts unless one with the same key columns is there already,
+// returns the known post in that case after counting the repost. Known rows created before
+// `since` are replaced as if they were never posted. Returns from the enclosing function.
+macro_rules! insert_or_existing {
+    ($conn:ident, $table:ident, $row:expr, $since:expr, $($key:ident),+) => {{
+        // the known row may be pruned before it is read, then the insert is retried
+        for _ in 0..UPSERT_ATTEMPTS {
+            let inserted = insert_into($table::table)
+                .values($row)
+                .on_conflict(($($table::$key),+))
+                .do_nothing()
+                .execute(&mut *$conn)
+                .await?;
+            if inserted > 0 {
+                return Ok(None);
+            }
+
+            let known = $table::table$(.filter($table::$key.eq(&$row.$key)))+;
+
+            if let Some(since) = $since {
+                // only one of concurrent posters manages to replace an expired row
+                let replaced = diesel::update(known.filter($table::created_at.lt(since)))
+                    .set((
+                        $table::message_url.eq(&$row.message_url),
+                        $table::poster_id.eq($row.poster_id),
+                        $table::poster_name.eq(&$row.poster_name),
+                        $table::repost_count.eq(0),
+                        $table::created_at.eq(Utc::now()),
+                    ))
+                    .execute(&mut *$conn)
+                    .await?;
+                if replaced > 0 {
+                    return Ok(None);
+                }
+            }
+
+            let original = diesel::update(known)
+                .set($table::repost_count.eq($table::repost_count + 1))
+                .returning((
+                    $table::message_url,
+                    $table::poster_id,
+                    $table::poster_name,
+                    $table::created_at,
+                    $table::repost_count,
+                ))
+                .get_result::<types::OriginalPost>(&mut *$conn)
+                .await
+                .optional()?;
+            if original.is_some() {
+                return Ok(original);
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "known row of {} is pruned on each of {UPSERT_ATTEMPTS} attempts",
+            stringify!($table)
+        ))
+    }};
+}
+
 #[derive(Clone)]
 pub struct AsyncRepository {
     pool: Pool<AsyncDieselConnectionManager<diesel_async::AsyncPgConnection>>,
@@ -45,65 +107,860 @@ impl AsyncRepository {
         Ok(tags
             .filter(text.eq(t))
             .inner_join(tag_to_media::table.inner_join(media::table))
-            .select((media::name, media::type_))
+            .select((media::name, media::type_, media::caption, media::parse_mode))
             .load::<types::MediaInfo>(&mut *conn)
             .await?)
     }
 
+    #[instrument(level = "trace", skip(self, m), fields(name = m.name))]
+    pub async fn insert_media(&mut self, m: &types::NewMedia) -> anyhow::Result<i32> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(insert_into(media)
+            .values(m)
+            .returning(media::id)
+            .get_result::<i32>(&mut *conn)
+            .await?)
+    }
+
+    /// Inserts the album together with its items, which refer to already known media.
+    #[instrument(level = "trace", skip(self, m, items), fields(name = m.name))]
+    pub async fn insert_album(
+        &mut self,
+        m: &types::NewMedia,
+        items: &[types::NewMediaGroupItem],
+    ) -> anyhow::Result<i32> {
+        use crate::schema::media_group_items;
+        use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection};
+
+        let names = items.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        let known = self.media_ids_by_names(&names).await?;
+        let mut item_ids = Vec::with_capacity(items.len());
+        for item in items {
+            match &known
+                .iter()
+                .filter(|x| x.0 == item.name)
+                .collect::<Vec<_>>()[..]
+            {
+                [(_, id_)] => item_ids.push(*id_),
+                _ => return Err(anyhow::anyhow!("media '{}' is ambiguous", item.name)),
+            }
+        }
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let group_id = insert_into(media)
+                        .values(m)
+                        .returning(media::id)
+                        .get_result::<i32>(conn)
+                        .await?;
+                    let rows = items
+                        .iter()
+                        .zip(item_ids)
+                        .enumerate()
+                        .map(|(i, (item, item_id))| {
+                            (
+                                media_group_items::group_media_id.eq(group_id),
+                                media_group_items::media_id.eq(item_id),
+                                media_group_items::position.eq(i as i32),
+                                media_group_items::caption.eq(item.caption.as_deref()),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    insert_into(media_group_items::table)
+                        .values(&rows)
+                        .execute(conn)
+                        .await?;
+                    Ok(group_id)
+                }
+                .scope_boxed()
+            })
+            .await?)
+    }
+
+    /// Looks up ids of the named media, several rows may share a name.
+    /// Fails if any of the names is unknown.
+    async fn media_ids_by_names(&mut self, names: &[String]) -> anyhow::Result<Vec<(String, i32)>> {
+        let mut requested = names.to_vec();
+        requested.sort();
+        requested.dedup();
+
+        let mut conn = self.pool.get().await?;
+
+        let known = media
+            .filter(name.eq_any(&requested))
+            .select((media::name, media::id))
+            .load::<(String, i32)>(&mut *conn)
+            .await?;
+        let unknown = requested
+            .iter()
+            .filter(|x| !known.iter().any(|(n, _)| n == *x))
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            return Err(anyhow::anyhow!("media {unknown:?} are unknown"));
+        }
+
+        Ok(known)
+    }
+
     #[instrument(level = "trace", skip(self))]
-    pub async fn media_data_by_name(&mut self, n: &str) -> anyhow::Result<Vec<u8>> {
+    pub async fn media_data_by_name(&mut self, n: &str) -> anyhow::Result<Option<Vec<u8>>> {
         let mut conn = self.pool.get().await?;
 
         Ok(media
             .filter(name.eq(n))
             .select(media::data)
-            .first::<Vec<u8>>(&mut *conn)
+            .first::<Option<Vec<u8>>>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self, d))]
+    pub async fn update_media_data(&mut self, n: &str, d: Option<&[u8]>) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(media.filter(name.eq(n)))
+            .set(data.eq(d))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_location_by_name(
+        &mut self,
+        n: &str,
+    ) -> anyhow::Result<types::MediaLocation> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(name.eq(n))
+            .select((media::id, media::name, media::storage, media::storage_key))
+            .first::<types::MediaLocation>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_locations_by_storage(
+        &mut self,
+        s: types::MediaStorage,
+    ) -> anyhow::Result<Vec<types::MediaLocation>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(storage.eq(s))
+            .filter(type_.ne_all(types::MediaType::WITHOUT_DATA))
+            .select((media::id, media::name, media::storage, media::storage_key))
+            .load::<types::MediaLocation>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn update_media_location(
+        &mut self,
+        id_: i32,
+        s: types::MediaStorage,
+        key: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(media.filter(media::id.eq(id_)))
+            .set((storage.eq(s), storage_key.eq(key)))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_file_id_by_name(&mut self, n: &str) -> anyhow::Result<Option<String>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(name.eq(n))
+            .select(media::telegram_file_id)
+            .first::<Option<String>>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn update_media_file_id(
+        &mut self,
+        n: &str,
+        file_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(media.filter(name.eq(n)))
+            .set(telegram_file_id.eq(file_id))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_payload_by_name(
+        &mut self,
+        n: &str,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(name.eq(n))
+            .select(media::payload)
+            .first::<Option<serde_json::Value>>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_metadata_by_name(
+        &mut self,
+        n: &str,
+    ) -> anyhow::Result<types::MediaMetadata> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(name.eq(n))
+            .select((
+                media::duration_secs,
+                media::width,
+                media::height,
+                media::thumbnail,
+                media::has_spoiler,
+                media::protect_content,
+            ))
+            .first::<types::MediaMetadata>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_group_items_by_name(
+        &mut self,
+        n: &str,
+    ) -> anyhow::Result<Vec<types::MediaGroupItem>> {
+        use crate::schema::media_group_items;
+
+        let mut conn = self.pool.get().await?;
+
+        let group_id = media
+            .filter(name.eq(n))
+            .select(media::id)
+            .first::<i32>(&mut *conn)
+            .await?;
+
+        Ok(media_group_items::table
+            .inner_join(media::table.on(media::id.eq(media_group_items::media_id)))
+            .filter(media_group_items::group_media_id.eq(group_id))
+            .order((media_group_items::position, media_group_items::id))
+            .select((media::name, media::type_, media_group_items::caption))
+            .load::<types::MediaGroupItem>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_script_steps_by_name(
+        &mut self,
+        n: &str,
+    ) -> anyhow::Result<Vec<types::ScriptStep>> {
+        use crate::schema::media_script_steps;
+
+        let mut conn = self.pool.get().await?;
+
+        let script_id = media
+            .filter(name.eq(n))
+            .select(media::id)
+            .first::<i32>(&mut *conn)
+            .await?;
+
+        Ok(media_script_steps::table
+            .left_join(media::table.on(media_script_steps::media_id.eq(media::id.nullable())))
+            .filter(media_script_steps::script_media_id.eq(script_id))
+            .order((media_script_steps::position, media_script_steps::id))
+            .select((
+                media_script_steps::kind,
+                media_script_steps::chat_action,
+                media_script_steps::min_delay_ms,
+                media_script_steps::max_delay_ms,
+                media::name.nullable(),
+                media::type_.nullable(),
+                media::caption.nullable(),
+                media::parse_mode.nullable(),
+            ))
+            .load::<types::ScriptStep>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn media_reply_ttl_by_name(&mut self, n: &str) -> anyhow::Result<Option<i32>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(media
+            .filter(name.eq(n))
+            .select(media::reply_ttl_secs)
+            .first::<Option<i32>>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn tag_reply_ttl_by_text(&mut self, t: &str) -> anyhow::Result<Option<i32>> {
+        use crate::schema::tags;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(tags::table
+            .filter(tags::text.eq(t))
+            .select(tags::reply_ttl_secs)
+            .first::<Option<i32>>(&mut *conn)
+            .await
+            .optional()?
+            .flatten())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn chat_reply_ttl_by_id(&mut self, c_id: i64) -> anyhow::Result<Option<i32>> {
+        use crate::schema::chats;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(chats::table
+            .filter(chats::chat_id.eq(c_id))
+            .select(chats::reply_ttl_secs)
+            .first::<Option<i32>>(&mut *conn)
+            .await
+            .optional()?
+            .flatten())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn tag_reply_options_by_text(
+        &mut self,
+        t: &str,
+    ) -> anyhow::Result<types::ReplyOptions> {
+        use crate::schema::tags;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(tags::table
+            .filter(tags::text.eq(t))
+            .select((tags::reply, tags::notify))
+            .first::<types::ReplyOptions>(&mut *conn)
+            .await
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn feature_reply_options(
+        &mut self,
+        t: types::MediaFeatureType,
+    ) -> anyhow::Result<types::ReplyOptions> {
+        use crate::schema::feature_reply_options;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(feature_reply_options::table
+            .filter(feature_reply_options::feature_type.eq(t))
+            .select((feature_reply_options::reply, feature_reply_options::notify))
+            .first::<types::ReplyOptions>(&mut *conn)
+            .await
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn chat_settings_by_id(
+        &mut self,
+        c_id: i64,
+    ) -> anyhow::Result<Option<types::ChatSettings>> {
+        use crate::schema::chats;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(chats::table
+            .filter(chats::chat_id.eq(c_id))
+            .select((
+                chats::text_trigger_enabled,
+                chats::duplicate_detection_enabled,
+                chats::cooldown_secs,
+                chats::text_duplicate_detection_enabled,
+            ))
+            .first::<types::ChatSettings>(&mut *conn)
+            .await
+            .optional()?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn topic_settings_by_ids(
+        &mut self,
+        c_id: i64,
+        t_id: i32,
+    ) -> anyhow::Result<Option<types::ChatSettings>> {
+        use crate::schema::topic_settings;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(topic_settings::table
+            .filter(topic_settings::chat_id.eq(c_id))
+            .filter(topic_settings::message_thread_id.eq(t_id))
+            .select((
+                topic_settings::text_trigger_enabled,
+                topic_settings::duplicate_detection_enabled,
+                topic_settings::cooldown_secs,
+                topic_settings::text_duplicate_detection_enabled,
+            ))
+            .first::<types::ChatSettings>(&mut *conn)
+            .await
+            .optional()?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn chat_duplicate_window_by_id(&mut self, c_id: i64) -> anyhow::Result<Option<i32>> {
+        use crate::schema::chats;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(chats::table
+            .filter(chats::chat_id.eq(c_id))
+            .select(chats::duplicate_window_days)
+            .first::<Option<i32>>(&mut *conn)
+            .await
+            .optional()?
+            .flatten())
+    }
+
+    /// Registers the chat or updates the known one, e.g. when the bot leaves it.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn upsert_chat(
+        &mut self,
+        c_id: i64,
+        t: Option<&str>,
+        is_active: bool,
+    ) -> anyhow::Result<()> {
+        use crate::schema::chats;
+        use diesel::upsert::excluded;
+
+        let mut conn = self.pool.get().await?;
+
+        insert_into(chats::table)
+            .values((
+                chats::chat_id.eq(c_id),
+                chats::title.eq(t),
+                chats::active.eq(is_active),
+            ))
+            .on_conflict(chats::chat_id)
+            .do_update()
+            .set((
+                chats::title.eq(excluded(chats::title)),
+                chats::active.eq(excluded(chats::active)),
+            ))
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn deactivate_chat(&mut self, c_id: i64) -> anyhow::Result<()> {
+        use crate::schema::chats;
+
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(chats::table.filter(chats::chat_id.eq(c_id)))
+            .set(chats::active.eq(false))
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Chats which the bot is a member of and which haven't opted out of broadcasts.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn broadcast_chat_ids(&mut self) -> anyhow::Result<Vec<i64>> {
+        use crate::schema::chats;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(chats::table
+            .filter(chats::active.eq(true))
+            .filter(chats::broadcast_enabled.eq(true))
+            .select(chats::chat_id)
+            .load::<i64>(&mut *conn)
             .await?)
     }
 
     #[instrument(level = "trace", skip(self))]
-    pub async fn forwarded_message_by_ids(
+    pub async fn chat_moderation_settings_by_id(
         &mut self,
         c_id: i64,
-        fwd_chat_id: i64,
-        msg_id: i32,
-    ) -> anyhow::Result<Option<types::ForwardedMessage>> {
+    ) -> anyhow::Result<Option<types::ModerationSettings>> {
+        use crate::schema::chats;
+
         let mut conn = self.pool.get().await?;
 
-        let result = forwarded_messages
-            .filter(chat_id.eq(c_id))
-            .filter(forwarded_message_id.eq(msg_id))
-            .filter(forwarded_chat_id.eq(fwd_chat_id))
+        Ok(chats::table
+            .filter(chats::chat_id.eq(c_id))
             .select((
-                forwarded_messages::chat_id,
-                forwarded_messages::forwarded_message_id,
-                forwarded_messages::message_url,
-                forwarded_messages::forwarded_chat_id,
+                chats::duplicate_policy,
+                chats::mute_after_duplicates,
+                chats::mute_window_hours,
+                chats::mute_minutes,
             ))
-            .first::<types::ForwardedMessage>(&mut conn)
-            .await;
+            .first::<types::ModerationSettings>(&mut *conn)
+            .await
+            .optional()?)
+    }
 
-        match result {
-            Ok(fm) => Ok(Some(fm)),
-            Err(diesel::NotFound) => Ok(None),
-            Err(e) => Err(anyhow::Error::new(e)),
+    /// Deletes messages kept for duplicate detection which are older than the window of their chat
+    /// or, if the chat has none, than the default one. Returns the number of deleted rows.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn prune_duplicate_candidates(
+        &mut self,
+        default_window_days: Option<i32>,
+    ) -> anyhow::Result<usize> {
+        let mut conn = self.pool.get().await?;
+
+        let mut count = 0;
+        for table in [
+            "forward_fingerprints",
+            "forwarded_messages",
+            "photo_hashes",
+            "posted_links",
+            "text_fingerprints",
+        ] {
+            // no window means rows are kept forever, comparisons with NULL are never true
+            count += diesel::sql_query(format!(
+                "DELETE FROM {table} t WHERE t.created_at < now() - make_interval(days => COALESCE(
+                    (SELECT c.duplicate_window_days FROM chats c WHERE c.chat_id = t.chat_id LIMIT 1),
+                    $1
+                ))"
+            ))
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Integer>, _>(default_window_days)
+            .execute(&mut *conn)
+            .await?;
         }
+
+        Ok(count)
     }
 
     #[instrument(level = "trace", skip(self))]
-    pub async fn insert_forward_message(
+    pub async fn insert_pending_deletion(
+        &mut self,
+        deletion: &types::NewPendingDeletion,
+    ) -> anyhow::Result<i32> {
+        use crate::schema::pending_deletions;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(insert_into(pending_deletions::table)
+            .values(deletion)
+            .returning(pending_deletions::id)
+            .get_result::<i32>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn pending_deletions(&mut self) -> anyhow::Result<Vec<types::PendingDeletion>> {
+        use crate::schema::pending_deletions;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(pending_deletions::table
+            .order(pending_deletions::delete_at)
+            .load::<types::PendingDeletion>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn delete_pending_deletion(&mut self, id_: i32) -> anyhow::Result<()> {
+        use crate::schema::pending_deletions;
+
+        let mut conn = self.pool.get().await?;
+
+        diesel::delete(pending_deletions::table.filter(pending_deletions::id.eq(id_)))
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Registers the forward unless it's known already, see `insert_or_existing!`.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_forward_message_or_existing(
         &mut self,
         message: &types::ForwardedMessage,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Option<types::OriginalPost>> {
+        let mut conn = self.pool.get().await?;
+
+        insert_or_existing!(
+            conn,
+            forwarded_messages,
+            message,
+            since,
+            chat_id,
+            forwarded_chat_id,
+            forwarded_message_id
+        )
+    }
+
+    /// Registers the forward unless it's known already, see `insert_or_existing!`.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_forward_fingerprint_or_existing(
+        &mut self,
+        fingerprint: &types::ForwardFingerprint,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Option<types::OriginalPost>> {
+        use crate::schema::forward_fingerprints;
+
+        let mut conn = self.pool.get().await?;
+
+        insert_or_existing!(
+            conn,
+            forward_fingerprints,
+            fingerprint,
+            since,
+            chat_id,
+            origin,
+            forward_date,
+            content_hash
+        )
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn photo_hashes_by_chat_id(
+        &mut self,
+        c_id: i64,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<types::PhotoHash>> {
+        use crate::schema::photo_hashes;
+
+        let mut conn = self.pool.get().await?;
+
+        let mut query = photo_hashes::table
+            .filter(photo_hashes::chat_id.eq(c_id))
+            .into_boxed();
+        if let Some(since) = since {
+            query = query.filter(photo_hashes::created_at.ge(since));
+        }
+
+        Ok(query
+            .select((
+                photo_hashes::chat_id,
+                photo_hashes::hash,
+                photo_hashes::message_url,
+                photo_hashes::poster_id,
+                photo_hashes::poster_name,
+            ))
+            .load::<types::PhotoHash>(&mut *conn)
+            .await?)
+    }
+
+    /// Counts one more repost of the photo posted by the message, returns its original post.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn register_photo_repost(
+        &mut self,
+        c_id: i64,
+        original_url: &str,
+    ) -> anyhow::Result<Option<types::OriginalPost>> {
+        use crate::schema::photo_hashes;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(diesel::update(photo_hashes::table)
+            .filter(photo_hashes::chat_id.eq(c_id))
+            .filter(photo_hashes::message_url.eq(original_url))
+            .set(photo_hashes::repost_count.eq(photo_hashes::repost_count + 1))
+            .returning((
+                photo_hashes::message_url,
+                photo_hashes::poster_id,
+                photo_hashes::poster_name,
+                photo_hashes::created_at,
+                photo_hashes::repost_count,
+            ))
+            .get_result::<types::OriginalPost>(&mut *conn)
+            .await
+            .optional()?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_photo_hash(&mut self, photo: &types::PhotoHash) -> anyhow::Result<()> {
+        use crate::schema::photo_hashes;
+
+        let mut conn = self.pool.get().await?;
+
+        insert_into(photo_hashes::table)
+            .values(photo)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn text_fingerprints_by_chat_id(
+        &mut self,
+        c_id: i64,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<types::TextFingerprint>> {
+        use crate::schema::text_fingerprints;
+
+        let mut conn = self.pool.get().await?;
+
+        let mut query = text_fingerprints::table
+            .filter(text_fingerprints::chat_id.eq(c_id))
+            .into_boxed();
+        if let Some(since) = since {
+            query = query.filter(text_fingerprints::created_at.ge(since));
+        }
+
+        Ok(query
+            .select((
+                text_fingerprints::chat_id,
+                text_fingerprints::fingerprint,
+                text_fingerprints::message_url,
+                text_fingerprints::poster_id,
+                text_fingerprints::poster_name,
+            ))
+            .load::<types::TextFingerprint>(&mut *conn)
+            .await?)
+    }
+
+    /// Counts one more repost of the text posted by the message, returns its original post.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn register_text_repost(
+        &mut self,
+        c_id: i64,
+        original_url: &str,
+    ) -> anyhow::Result<Option<types::OriginalPost>> {
+        use crate::schema::text_fingerprints;
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(diesel::update(text_fingerprints::table)
+            .filter(text_fingerprints::chat_id.eq(c_id))
+            .filter(text_fingerprints::message_url.eq(original_url))
+            .set(text_fingerprints::repost_count.eq(text_fingerprints::repost_count + 1))
+            .returning((
+                text_fingerprints::message_url,
+                text_fingerprints::poster_id,
+                text_fingerprints::poster_name,
+                text_fingerprints::created_at,
+                text_fingerprints::repost_count,
+            ))
+            .get_result::<types::OriginalPost>(&mut *conn)
+            .await
+            .optional()?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_text_fingerprint(
+        &mut self,
+        text: &types::TextFingerprint,
+    ) -> anyhow::Result<()> {
+        use crate::schema::text_fingerprints;
+
+        let mut conn = self.pool.get().await?;
+
+        insert_into(text_fingerprints::table)
+            .values(text)
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Registers the link unless it's known already, see `insert_or_existing!`.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_posted_link_or_existing(
+        &mut self,
+        link: &types::PostedLink,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Option<types::OriginalPost>> {
+        use crate::schema::posted_links;
+
+        let mut conn = self.pool.get().await?;
+
+        insert_or_existing!(conn, posted_links, link, since, chat_id, url)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_repost_event(
+        &mut self,
+        event: &types::NewRepostEvent,
     ) -> anyhow::Result<()> {
+        use crate::schema::repost_events;
+
         let mut conn = self.pool.get().await?;
 
-        insert_into(forwarded_messages)
-            .values(message)
+        insert_into(repost_events::table)
+            .values(event)
             .execute(&mut *conn)
             .await?;
+
         Ok(())
     }
 
+    /// Users who have reposted the most in the chat since the given time, the most active first.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn top_reposters(
+        &mut self,
+        c_id: i64,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<types::ReposterStats>> {
+        use crate::schema::repost_events;
+        use diesel::dsl::{count_star, sql};
+        use diesel::sql_types::{Nullable, Varchar};
+
+        let mut conn = self.pool.get().await?;
+
+        // reposters may have been renamed, the latest name is shown
+        let latest_name =
+            sql::<Nullable<Varchar>>("(array_agg(reposter_name ORDER BY created_at DESC))[1]");
+
+        Ok(repost_events::table
+            .filter(repost_events::chat_id.eq(c_id))
+            .filter(repost_events::created_at.ge(since))
+            .filter(repost_events::reposter_id.is_not_null())
+            .group_by(repost_events::reposter_id)
+            .select((repost_events::reposter_id, latest_name, count_star()))
+            .order_by(count_star().desc())
+            .limit(limit)
+            .load::<types::ReposterStats>(&mut *conn)
+            .await?)
+    }
+
+    /// How many times the user has reposted something in the chat since the given time
+    /// and how many times posts of the user have been reposted by others.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn user_repost_counts(
+        &mut self,
+        c_id: i64,
+        u_id: i64,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<(i64, i64)> {
+        use crate::schema::repost_events;
+
+        let mut conn = self.pool.get().await?;
+
+        let in_chat = repost_events::table
+            .filter(repost_events::chat_id.eq(c_id))
+            .filter(repost_events::created_at.ge(since));
+        let reposts = in_chat
+            .filter(repost_events::reposter_id.eq(u_id))
+            .count()
+            .get_result::<i64>(&mut *conn)
+            .await?;
+        let reposted = in_chat
+            .filter(repost_events::original_poster_id.eq(u_id))
+            .filter(repost_events::reposter_id.ne(u_id))
+            .count()
+            .get_result::<i64>(&mut *conn)
+            .await?;
+
+        Ok((reposts, reposted))
+    }
+
     #[instrument(level = "trace", skip(self))]
     pub async fn media_info_by_feature_type(
         &mut self,
@@ -116,18 +973,114 @@ impl AsyncRepository {
         Ok(media
             .inner_join(media_to_feature::table)
             .filter(media_to_feature::feature_type.eq(t))
-            .select((media::name, media::type_))
+            .select((media::name, media::type_, media::caption, media::parse_mode))
             .load::<types::MediaInfo>(&mut *conn)
             .await?)
     }
 
+    /// Cron jobs with time zones of their chats if they have none of their own,
+    /// one-shot jobs which have been sent already are omitted.
     #[instrument(level = "trace", skip(self))]
     pub async fn cron_jobs(&mut self) -> anyhow::Result<Vec<types::CroneJob>> {
-        use crate::schema::cron_jobs::dsl::*;
+        use crate::schema::{chats, cron_jobs};
+        use diesel::sql_types::{Nullable, Varchar};
+
+        sql_function!(fn coalesce(x: Nullable<Varchar>, y: Nullable<Varchar>) -> Nullable<Varchar>);
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(cron_jobs::table
+            .left_join(chats::table.on(cron_jobs::chat_id.eq(chats::chat_id.nullable())))
+            .filter(cron_jobs::sent_at.is_null())
+            .filter(cron_jobs::skipped_at.is_null())
+            .select((
+                cron_jobs::id,
+                cron_jobs::pattern,
+                cron_jobs::chat_id,
+                cron_jobs::caption,
+                cron_jobs::description,
+                cron_jobs::parse_mode,
+                cron_jobs::message_thread_id,
+                coalesce(cron_jobs::time_zone, chats::time_zone.nullable()),
+                cron_jobs::run_at,
+            ))
+            .load::<types::CroneJob>(&mut *conn)
+            .await?)
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn mark_cron_job_sent(&mut self, id_: i32) -> anyhow::Result<()> {
+        use crate::schema::cron_jobs;
 
         let mut conn = self.pool.get().await?;
 
-        Ok(cron_jobs.load::<types::CroneJob>(&mut *conn).await?)
+        diesel::update(cron_jobs::table.filter(cron_jobs::id.eq(id_)))
+            .set(cron_jobs::sent_at.eq(Utc::now()))
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn mark_cron_job_skipped(&mut self, id_: i32) -> anyhow::Result<()> {
+        use crate::schema::cron_jobs;
+
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(cron_jobs::table.filter(cron_jobs::id.eq(id_)))
+            .set(cron_jobs::skipped_at.eq(Utc::now()))
+            .execute(&mut *conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds the job along with the media to choose from, returns the id of the job.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn insert_cron_job(
+        &mut self,
+        job: &types::NewCronJob,
+        media_names: &[String],
+    ) -> anyhow::Result<i32> {
+        use crate::schema::{cron_jobs, media_to_cron_job};
+        use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection};
+
+        let media_ids = self
+            .media_ids_by_names(media_names)
+            .await?
+            .into_iter()
+            .map(|(_, id_)| id_)
+            .collect::<Vec<_>>();
+
+        let mut conn = self.pool.get().await?;
+
+        Ok(conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let job_id = insert_into(cron_jobs::table)
+                        .values(job)
+                        .returning(cron_jobs::id)
+                        .get_result::<i32>(conn)
+                        .await?;
+                    let links = media_ids
+                        .into_iter()
+                        .map(|x| {
+                            (
+                                media_to_cron_job::media_id.eq(x),
+                                media_to_cron_job::cron_job_id.eq(job_id),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    insert_into(media_to_cron_job::table)
+                        .values(&links)
+                        .execute(conn)
+                        .await?;
+                    Ok(job_id)
+                }
+                .scope_boxed()
+            })
+            .await?)
     }
 
     #[instrument(level = "trace", skip(self))]
@@ -144,7 +1097,7 @@ impl AsyncRepository {
         Ok(cron_jobs
             .filter(cron_jobs::id.eq(id_))
             .inner_join(media_to_cron_job::table.inner_join(media::table))
-            .select((media::name, media::type_))
+            .select((media::name, media::type_, media::caption, media::parse_mode))
             .load::<types::MediaInfo>(&mut *conn)
             .await?)
     }
@@ -1,7 +1,12 @@
 use diesel::prelude::*;
 use diesel_derive_enum::*;
 
-use crate::schema::forwarded_messages;
+use chrono::{DateTime, Utc};
+
+use crate::schema::{
+    cron_jobs, forward_fingerprints, forwarded_messages, media, pending_deletions, photo_hashes,
+    posted_links, repost_events, text_fingerprints,
+};
 
 #[derive(Debug, PartialEq, Eq, DbEnum, Clone)]
 #[ExistingTypePath = "crate::schema::sql_types::TagType"]
@@ -22,6 +27,55 @@ pub enum MediaType {
     VideoNote,
     Sticker,
     Unknown,
+    Album,
+    Script,
+    Audio,
+    Location,
+    Venue,
+    Dice,
+    Poll,
+    Contact,
+}
+
+impl MediaType {
+    /// Types of media which have no blob, they are composed of other media or described by a payload.
+    pub const WITHOUT_DATA: [MediaType; 7] = [
+        MediaType::Album,
+        MediaType::Script,
+        MediaType::Location,
+        MediaType::Venue,
+        MediaType::Dice,
+        MediaType::Poll,
+        MediaType::Contact,
+    ];
+
+    pub fn has_data(&self) -> bool {
+        !Self::WITHOUT_DATA.contains(self)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, DbEnum, Clone, Copy)]
+#[ExistingTypePath = "crate::schema::sql_types::MediaStorage"]
+pub enum MediaStorage {
+    Postgres,
+    Filesystem,
+    S3,
+}
+
+#[derive(Debug, PartialEq, Eq, DbEnum, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::TextParseMode"]
+pub enum TextParseMode {
+    None,
+    MarkdownV2,
+    Html,
+}
+
+#[derive(Debug, PartialEq, Eq, DbEnum, Clone)]
+#[ExistingTypePath = "crate::schema::sql_types::ScriptStepKind"]
+pub enum ScriptStepKind {
+    ChatAction,
+    Delay,
+    Media,
 }
 
 #[derive(Debug, PartialEq, Eq, DbEnum, Clone)]
@@ -31,6 +85,15 @@ pub enum MediaFeatureType {
     DuplicatedForwardedMessageDetection,
 }
 
+#[derive(Debug, PartialEq, Eq, DbEnum, Clone, Copy, Default)]
+#[ExistingTypePath = "crate::schema::sql_types::DuplicatePolicy"]
+pub enum DuplicatePolicy {
+    #[default]
+    Reply,
+    Delete,
+    Escalate,
+}
+
 #[derive(Queryable, Clone)]
 pub struct Tag {
     pub text: String,
@@ -42,6 +105,67 @@ pub struct Tag {
 pub struct MediaInfo {
     pub name: String,
     pub type_: MediaType,
+    pub caption: Option<String>,
+    pub parse_mode: TextParseMode,
+}
+
+#[derive(Insertable, Clone)]
+#[diesel(table_name = media)]
+pub struct NewMedia {
+    pub name: String,
+    pub type_: MediaType,
+    pub data: Option<Vec<u8>>,
+    pub caption: Option<String>,
+    pub parse_mode: TextParseMode,
+    pub payload: Option<serde_json::Value>,
+    #[diesel(embed)]
+    pub metadata: MediaMetadata,
+}
+
+/// An item of an album being added, referring to another media by its name.
+#[derive(Clone, Debug)]
+pub struct NewMediaGroupItem {
+    pub name: String,
+    pub caption: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Clone, Default, Debug)]
+#[diesel(table_name = media)]
+pub struct MediaMetadata {
+    pub duration_secs: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// JPEG of up to 320x320 pixels and 200 kB.
+    pub thumbnail: Option<Vec<u8>>,
+    pub has_spoiler: bool,
+    pub protect_content: bool,
+}
+
+#[derive(Queryable, Clone)]
+pub struct MediaGroupItem {
+    pub name: String,
+    pub type_: MediaType,
+    pub caption: Option<String>,
+}
+
+#[derive(Queryable, Clone)]
+pub struct ScriptStep {
+    pub kind: ScriptStepKind,
+    pub chat_action: Option<String>,
+    pub min_delay_ms: Option<i32>,
+    pub max_delay_ms: Option<i32>,
+    pub media_name: Option<String>,
+    pub media_type: Option<MediaType>,
+    pub media_caption: Option<String>,
+    pub media_parse_mode: Option<TextParseMode>,
+}
+
+#[derive(Queryable, Clone, Debug)]
+pub struct MediaLocation {
+    pub id: i32,
+    pub name: String,
+    pub storage: MediaStorage,
+    pub storage_key: Option<String>,
 }
 
 #[derive(Queryable, Clone, Insertable, Debug)]
@@ -51,13 +175,195 @@ pub struct ForwardedMessage {
     pub forwarded_message_id: i32,
     pub message_url: String,
     pub forwarded_chat_id: i64,
+    pub poster_id: Option<i64>,
+    pub poster_name: Option<String>,
 }
 
-#[derive(Queryable, Clone)]
+#[derive(Queryable, Insertable, Clone, Debug, PartialEq)]
+#[diesel(table_name = forward_fingerprints)]
+pub struct ForwardFingerprint {
+    pub chat_id: i64,
+    /// Who the original message was sent by, e.g. `user:42` or `sender:John Doe`.
+    pub origin: String,
+    pub forward_date: DateTime<Utc>,
+    /// Bits of the unsigned hash of the text and the files.
+    pub content_hash: i64,
+    pub message_url: String,
+    pub poster_id: Option<i64>,
+    pub poster_name: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Clone, Debug)]
+#[diesel(table_name = photo_hashes)]
+pub struct PhotoHash {
+    pub chat_id: i64,
+    /// Bits of the unsigned perceptual hash.
+    pub hash: i64,
+    pub message_url: String,
+    pub poster_id: Option<i64>,
+    pub poster_name: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Clone, Debug)]
+#[diesel(table_name = posted_links)]
+pub struct PostedLink {
+    pub chat_id: i64,
+    /// Canonical form of the link.
+    pub url: String,
+    pub message_url: String,
+    pub poster_id: Option<i64>,
+    pub poster_name: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Clone, Debug)]
+#[diesel(table_name = text_fingerprints)]
+pub struct TextFingerprint {
+    pub chat_id: i64,
+    /// Bits of the unsigned SimHash.
+    pub fingerprint: i64,
+    pub message_url: String,
+    pub poster_id: Option<i64>,
+    pub poster_name: Option<String>,
+}
+
+/// The first post of an item which has been posted to the chat again.
+#[derive(Queryable, Clone, Debug, PartialEq)]
+pub struct OriginalPost {
+    pub message_url: String,
+    pub poster_id: Option<i64>,
+    /// Unknown for items registered before posters were recorded.
+    pub poster_name: Option<String>,
+    pub posted_at: DateTime<Utc>,
+    /// How many times the item has been posted again, including the latest time.
+    pub repost_count: i32,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = repost_events)]
+pub struct NewRepostEvent {
+    pub chat_id: i64,
+    pub reposter_id: Option<i64>,
+    pub reposter_name: Option<String>,
+    pub message_url: String,
+    pub original_url: String,
+    pub original_poster_id: Option<i64>,
+}
+
+/// How many times the user has reposted something.
+#[derive(Queryable, Clone, Debug, PartialEq)]
+pub struct ReposterStats {
+    pub reposter_id: Option<i64>,
+    pub reposter_name: Option<String>,
+    pub repost_count: i64,
+}
+
+#[derive(Queryable, Clone, Debug)]
+pub struct PendingDeletion {
+    pub id: i32,
+    pub chat_id: i64,
+    pub message_id: i32,
+    pub delete_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = pending_deletions)]
+pub struct NewPendingDeletion {
+    pub chat_id: i64,
+    pub message_id: i32,
+    pub delete_at: DateTime<Utc>,
+}
+
+/// How a response treats the message which triggered it, unset options fall back to defaults.
+#[derive(Queryable, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ReplyOptions {
+    /// Quote the trigger message rather than just send to the chat.
+    pub reply: Option<bool>,
+    /// Notify chat members rather than send silently.
+    pub notify: Option<bool>,
+}
+
+impl ReplyOptions {
+    /// Takes options which are unset here from the other ones.
+    pub fn or(self, other: ReplyOptions) -> ReplyOptions {
+        ReplyOptions {
+            reply: self.reply.or(other.reply),
+            notify: self.notify.or(other.notify),
+        }
+    }
+}
+
+/// Settings of a chat or of a forum topic, unset ones are inherited.
+#[derive(Queryable, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ChatSettings {
+    pub text_trigger_enabled: Option<bool>,
+    pub duplicate_detection_enabled: Option<bool>,
+    pub cooldown_secs: Option<i32>,
+    pub text_duplicate_detection_enabled: Option<bool>,
+}
+
+impl ChatSettings {
+    /// Takes settings which are unset here from the other ones.
+    pub fn or(self, other: ChatSettings) -> ChatSettings {
+        ChatSettings {
+            text_trigger_enabled: self.text_trigger_enabled.or(other.text_trigger_enabled),
+            duplicate_detection_enabled: self
+                .duplicate_detection_enabled
+                .or(other.duplicate_detection_enabled),
+            cooldown_secs: self.cooldown_secs.or(other.cooldown_secs),
+            text_duplicate_detection_enabled: self
+                .text_duplicate_detection_enabled
+                .or(other.text_duplicate_detection_enabled),
+        }
+    }
+}
+
+/// How duplicates are dealt with in a chat, unset limits fall back to defaults.
+#[derive(Queryable, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct ModerationSettings {
+    pub policy: DuplicatePolicy,
+    /// Reposters are muted on this duplicate within the window.
+    pub mute_after_duplicates: Option<i32>,
+    pub mute_window_hours: Option<i32>,
+    pub mute_minutes: Option<i32>,
+}
+
+#[derive(Queryable, Clone, Debug, PartialEq)]
 pub struct CroneJob {
     pub id: i32,
-    pub pattern: String,
+    /// Recurring jobs have a pattern, one-shot ones have a time to run at instead.
+    pub pattern: Option<String>,
     pub chat_id: Option<i64>,
     pub caption: Option<String>,
     pub description: Option<String>,
+    pub parse_mode: TextParseMode,
+    /// Forum topic to post to, the General one is used if there is none.
+    pub message_thread_id: Option<i32>,
+    /// IANA time zone of the pattern, the one of the chat or UTC if unset.
+    /// Broadcasts are sent at once to all chats, so they never follow time zones of chats.
+    pub time_zone: Option<String>,
+    pub run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = cron_jobs)]
+pub struct NewCronJob {
+    pub pattern: Option<String>,
+    pub run_at: Option<DateTime<Utc>>,
+    pub chat_id: Option<i64>,
+    pub message_thread_id: Option<i32>,
+    pub caption: Option<String>,
+    pub description: Option<String>,
+    pub parse_mode: TextParseMode,
+    pub time_zone: Option<String>,
+}
+
+impl CroneJob {
+    /// The pattern or the time to run at, to tell jobs apart in logs.
+    pub fn schedule(&self) -> String {
+        match (&self.pattern, self.run_at) {
+            (Some(pattern), _) => pattern.clone(),
+            (None, Some(run_at)) => format!("at {run_at}"),
+            (None, None) => format!("#{}", self.id),
+        }
+    }
 }
@@ -0,0 +1,396 @@
+use anyhow::anyhow;
+use teloxide::types::ParseMode;
+
+use crate::database::types::TextParseMode;
+
+const MARKDOWN_V2_RESERVED: &str = "_*[]()~`>#+-=|{}.!\\";
+const HTML_TAGS: [&str; 15] = [
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "ins",
+    "s",
+    "strike",
+    "del",
+    "span",
+    "tg-spoiler",
+    "a",
+    "code",
+    "pre",
+    "blockquote",
+];
+
+pub fn telegram_parse_mode(mode: &TextParseMode) -> Option<ParseMode> {
+    match mode {
+        TextParseMode::None => None,
+        TextParseMode::MarkdownV2 => Some(ParseMode::MarkdownV2),
+        TextParseMode::Html => Some(ParseMode::Html),
+    }
+}
+
+/// Checks that the text would be accepted by Telegram with the given parse mode.
+pub fn validate(text: &str, mode: &TextParseMode) -> anyhow::Result<()> {
+    match mode {
+        TextParseMode::None => Ok(()),
+        TextParseMode::MarkdownV2 => validate_markdown_v2(text),
+        TextParseMode::Html => validate_html(text),
+    }
+}
+
+pub fn escape(text: &str, mode: &TextParseMode) -> String {
+    match mode {
+        TextParseMode::None => text.to_string(),
+        TextParseMode::MarkdownV2 => {
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                if MARKDOWN_V2_RESERVED.contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+        TextParseMode::Html => text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    }
+}
+
+/// Appends a plain text line to an already formatted text.
+pub fn append_escaped(formatted: Option<&str>, plain: &str, mode: &TextParseMode) -> String {
+    match formatted {
+        Some(formatted) if !formatted.is_empty() => {
+            format!("{formatted}\n{}", escape(plain, mode))
+        }
+        _ => escape(plain, mode),
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum MarkdownEntity {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    LinkText,
+}
+
+fn validate_markdown_v2(text: &str) -> anyhow::Result<()> {
+    let chars: Vec<_> = text.chars().collect();
+    let mut stack = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let entity = match c {
+            '\\' => {
+                match chars.get(i + 1) {
+                    Some(&x) if (1..=126).contains(&(x as u32)) => i += 2,
+                    _ => return Err(anyhow!("Dangling '\\' at {i}")),
+                }
+                continue;
+            }
+            '`' => {
+                i = skip_markdown_code(&chars, i)?;
+                continue;
+            }
+            '*' => MarkdownEntity::Bold,
+            '~' => MarkdownEntity::Strikethrough,
+            '_' => {
+                // '__' is always treated greedily as underline
+                if chars.get(i + 1) == Some(&'_') {
+                    i += 1;
+                    MarkdownEntity::Underline
+                } else {
+                    MarkdownEntity::Italic
+                }
+            }
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                i += 1;
+                MarkdownEntity::Spoiler
+            }
+            '[' => {
+                stack.push(MarkdownEntity::LinkText);
+                i += 1;
+                continue;
+            }
+            ']' => {
+                if stack.pop() != Some(MarkdownEntity::LinkText) {
+                    return Err(anyhow!("Unexpected ']' at {i}"));
+                }
+                i = skip_markdown_link_url(&chars, i + 1)?;
+                continue;
+            }
+            '>' if i == 0 || chars[i - 1] == '\n' => {
+                i += 1;
+                continue;
+            }
+            c if MARKDOWN_V2_RESERVED.contains(c) => {
+                return Err(anyhow!("Character '{c}' at {i} must be escaped"));
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        if stack.last() == Some(&entity) {
+            stack.pop();
+        } else if stack.contains(&entity) {
+            return Err(anyhow!("Entity {entity:?} at {i} is not properly nested"));
+        } else {
+            stack.push(entity);
+        }
+        i += 1;
+    }
+
+    match stack.last() {
+        Some(entity) => Err(anyhow!("Entity {entity:?} is not closed")),
+        None => Ok(()),
+    }
+}
+
+// Returns the index right after the closing backtick(s).
+fn skip_markdown_code(chars: &[char], start: usize) -> anyhow::Result<usize> {
+    let is_pre = chars[start..].starts_with(&['`', '`', '`']);
+    let fence = if is_pre { 3 } else { 1 };
+
+    let mut i = start + fence;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => i += 2,
+            '`' if !is_pre => return Ok(i + 1),
+            '`' if chars[i..].starts_with(&['`', '`', '`']) => return Ok(i + 3),
+            '`' => return Err(anyhow!("Character '`' at {i} must be escaped")),
+            _ => i += 1,
+        }
+    }
+
+    Err(anyhow!("Code block at {start} is not closed"))
+}
+
+// Returns the index right after the link url or after the link text if there is no url.
+fn skip_markdown_link_url(chars: &[char], start: usize) -> anyhow::Result<usize> {
+    if chars.get(start) != Some(&'(') {
+        return Err(anyhow!("Link at {start} has no url"));
+    }
+
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => i += 2,
+            ')' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+
+    Err(anyhow!("Link url at {start} is not closed"))
+}
+
+fn validate_html(text: &str) -> anyhow::Result<()> {
+    let mut stack: Vec<String> = Vec::new();
+    let mut rest = text;
+
+    while let Some(idx) = rest.find(['<', '>', '&']) {
+        let tail = &rest[idx..];
+        rest = match tail.as_bytes()[0] {
+            b'&' => {
+                let end = tail
+                    .find(';')
+                    .ok_or_else(|| anyhow!("Character '&' must be escaped"))?;
+                let entity = &tail[1..end];
+                let is_valid = matches!(entity, "lt" | "gt" | "amp" | "quot")
+                    || entity
+                        .strip_prefix("#x")
+                        .map(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_hexdigit()))
+                        .or_else(|| {
+                            entity
+                                .strip_prefix('#')
+                                .map(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()))
+                        })
+                        .unwrap_or(false);
+                if !is_valid {
+                    return Err(anyhow!("Unsupported HTML entity '&{entity};'"));
+                }
+                &tail[end + 1..]
+            }
+            b'<' => {
+                let end = html_tag_end(tail)?;
+                check_html_tag(&tail[1..end], &mut stack)?;
+                &tail[end + 1..]
+            }
+            _ => return Err(anyhow!("Character '>' must be escaped")),
+        };
+    }
+
+    match stack.last() {
+        Some(tag) => Err(anyhow!("Tag <{tag}> is not closed")),
+        None => Ok(()),
+    }
+}
+
+fn html_tag_end(tag: &str) -> anyhow::Result<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices().skip(1) {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return Ok(i),
+            ('<', None) => return Err(anyhow!("Character '<' must be escaped")),
+            _ => {}
+        }
+    }
+
+    Err(anyhow!("Tag '{tag}' is not closed"))
+}
+
+fn check_html_tag(tag: &str, stack: &mut Vec<String>) -> anyhow::Result<()> {
+    if let Some(name) = tag.strip_prefix('/') {
+        let name = name.trim().to_lowercase();
+        return match stack.pop() {
+            Some(open) if open == name => Ok(()),
+            Some(open) => Err(anyhow!("Tag </{name}> closes <{open}>")),
+            None => Err(anyhow!("Tag </{name}> closes nothing")),
+        };
+    }
+
+    let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let name = name.to_lowercase();
+    if !HTML_TAGS.contains(&name.as_str()) {
+        return Err(anyhow!("Unsupported tag <{name}>"));
+    }
+
+    let attributes = attributes.replace(' ', "").to_lowercase();
+    match name.as_str() {
+        "a" if !attributes.starts_with("href=") => {
+            return Err(anyhow!("Tag <a> must have 'href' attribute"))
+        }
+        "span"
+            if !matches!(
+                attributes.as_str(),
+                "class=\"tg-spoiler\"" | "class='tg-spoiler'"
+            ) =>
+        {
+            return Err(anyhow!("Tag <span> must have 'tg-spoiler' class"))
+        }
+        _ => {}
+    }
+
+    stack.push(name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::types::TextParseMode;
+
+    use super::{append_escaped, escape, validate};
+
+    #[test]
+    fn test_valid_markdown_v2() {
+        for text in [
+            "plain text",
+            "*bold* _italic_ __underline__ ~strike~ ||spoiler||",
+            "*bold _italic bold ~italic bold strike ||spoiler||~ __underline___ bold*",
+            "escaped \\. \\! \\( \\) \\- \\\\",
+            "[link](http://www.example.com/?a=\\)b)",
+            "`inline code with * and _`",
+            "```rust\nfn main() { println!(\"\\`\"); }\n```",
+            "> quote\n>another line",
+            "юнікод\\.",
+        ] {
+            assert!(
+                validate(text, &TextParseMode::MarkdownV2).is_ok(),
+                "'{text}' should be valid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_markdown_v2() {
+        for text in [
+            "unescaped dot.",
+            "*unclosed bold",
+            "*bold _italic* unclosed_",
+            "[link without url]",
+            "[link](http://unclosed",
+            "`unclosed code",
+            "```unclosed pre",
+            "dangling \\",
+            "a > b",
+            "single | pipe",
+        ] {
+            assert!(
+                validate(text, &TextParseMode::MarkdownV2).is_err(),
+                "'{text}' should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_valid_html() {
+        for text in [
+            "plain text",
+            "<b>bold</b> <i>italic</i> <u>underline</u> <s>strike</s>",
+            "<b>bold <i>italic bold</i></b>",
+            "<span class=\"tg-spoiler\">spoiler</span> <tg-spoiler>spoiler</tg-spoiler>",
+            "<a href=\"http://www.example.com/?a=1&amp;b=2\">link</a>",
+            "<pre><code class=\"language-rust\">1 &lt; 2</code></pre>",
+            "&lt; &gt; &amp; &quot; &#39; &#x27;",
+        ] {
+            assert!(
+                validate(text, &TextParseMode::Html).is_ok(),
+                "'{text}' should be valid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_html() {
+        for text in [
+            "1 < 2",
+            "2 > 1",
+            "fish & chips",
+            "&nbsp;",
+            "<b>unclosed",
+            "<b><i>wrong nesting</b></i>",
+            "</b>",
+            "<div>unsupported</div>",
+            "<a>no href</a>",
+            "<span>no class</span>",
+            "<b unclosed tag",
+        ] {
+            assert!(
+                validate(text, &TextParseMode::Html).is_err(),
+                "'{text}' should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_plain_text_is_always_valid() {
+        assert!(validate("*<unclosed & unescaped.", &TextParseMode::None).is_ok());
+    }
+
+    #[test]
+    fn test_escaped_text_is_valid() {
+        let text = "*<b>1 & 2</b>* [x](y) `z`. \\";
+        for mode in [TextParseMode::MarkdownV2, TextParseMode::Html] {
+            assert!(validate(&escape(text, &mode), &mode).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_append_escaped() {
+        assert_eq!(
+            append_escaped(Some("*look*"), "t.me/c/1", &TextParseMode::MarkdownV2),
+            "*look*\nt\\.me/c/1"
+        );
+        assert_eq!(append_escaped(None, "a&b", &TextParseMode::Html), "a&amp;b");
+    }
+}
@@ -0,0 +1,254 @@
+use crate::database::types::MediaType;
+
+/// Metadata which could be read from the file itself.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct DetectedMetadata {
+    pub duration_secs: Option<i32>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+const OPUS_SAMPLE_RATE: u64 = 48000;
+
+/// Reads duration and dimensions of pictures, stickers, GIFs, MP4 videos and Ogg Opus voices.
+/// Anything which can't be recognized is left empty.
+pub fn detect_metadata(type_: &MediaType, data: &[u8]) -> DetectedMetadata {
+    match type_ {
+        MediaType::Picture | MediaType::Sticker => image_metadata(data),
+        MediaType::Video | MediaType::VideoNote => mp4_metadata(data),
+        MediaType::Animation => mp4_metadata(data).or_else(|| image_metadata(data)),
+        MediaType::Voice | MediaType::Audio => ogg_opus_metadata(data),
+        _ => None,
+    }
+    .unwrap_or_default()
+}
+
+fn image_metadata(data: &[u8]) -> Option<DetectedMetadata> {
+    let size = imagesize::blob_size(data).ok()?;
+    Some(DetectedMetadata {
+        duration_secs: None,
+        width: size.width.try_into().ok(),
+        height: size.height.try_into().ok(),
+    })
+}
+
+fn mp4_metadata(data: &[u8]) -> Option<DetectedMetadata> {
+    let moov = find_box(data, b"moov")?;
+
+    let mvhd = find_box(moov, b"mvhd")?;
+    let (timescale, duration) = match *mvhd.first()? {
+        0 => (read_u32(mvhd, 12)? as u64, read_u32(mvhd, 16)? as u64),
+        1 => (read_u32(mvhd, 20)? as u64, read_u64(mvhd, 24)?),
+        _ => return None,
+    };
+    let duration_secs = (timescale != 0)
+        .then(|| (duration + timescale / 2) / timescale)
+        .and_then(|x| i32::try_from(x).ok());
+
+    // audio tracks have zero dimensions, the first video track is taken
+    let (width, height) = boxes(moov)
+        .filter(|(type_, _)| type_ == b"trak")
+        .filter_map(|(_, trak)| find_box(trak, b"tkhd"))
+        .filter_map(|tkhd| {
+            let offset = match *tkhd.first()? {
+                0 => 76,
+                1 => 88,
+                _ => return None,
+            };
+            // 16.16 fixed-point numbers
+            let width = read_u32(tkhd, offset)? >> 16;
+            let height = read_u32(tkhd, offset + 4)? >> 16;
+            Some((width, height))
+        })
+        .find(|(width, height)| *width != 0 && *height != 0)
+        .map_or((None, None), |(width, height)| {
+            (width.try_into().ok(), height.try_into().ok())
+        });
+
+    Some(DetectedMetadata {
+        duration_secs,
+        width,
+        height,
+    })
+}
+
+// Iterates over ISO BMFF boxes, yields their types and bodies.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = read_u32(data, 0)? as u64;
+        let type_: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (8, data.len() as u64),
+            1 => (16, read_u64(data, 8)?),
+            x => (8, x),
+        };
+        let size = usize::try_from(size).ok()?;
+        if size < header || size > data.len() {
+            return None;
+        }
+
+        let body = &data[header..size];
+        data = &data[size..];
+        Some((type_, body))
+    })
+}
+
+fn find_box<'a>(data: &'a [u8], type_: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(t, _)| t == type_).map(|(_, body)| body)
+}
+
+fn ogg_opus_metadata(data: &[u8]) -> Option<DetectedMetadata> {
+    if !data.starts_with(b"OggS") {
+        return None;
+    }
+
+    let head = find(data, b"OpusHead")?;
+    let pre_skip = u16::from_le_bytes(data.get(head + 10..head + 12)?.try_into().ok()?);
+
+    // the granule position of the last page is the number of samples at 48 kHz
+    let last_page = data.windows(4).rposition(|x| x == b"OggS")?;
+    let granule = u64::from_le_bytes(data.get(last_page + 6..last_page + 14)?.try_into().ok()?);
+    let samples = granule.saturating_sub(pre_skip as u64);
+
+    Some(DetectedMetadata {
+        duration_secs: i32::try_from((samples + OPUS_SAMPLE_RATE / 2) / OPUS_SAMPLE_RATE).ok(),
+        width: None,
+        height: None,
+    })
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|x| x == needle)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::types::MediaType;
+
+    use super::{detect_metadata, DetectedMetadata};
+
+    fn mp4_box(type_: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(type_);
+        b.extend_from_slice(body);
+        b
+    }
+
+    fn mvhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut body = vec![0; 12];
+        body.extend_from_slice(&timescale.to_be_bytes());
+        body.extend_from_slice(&duration.to_be_bytes());
+        body.resize(100, 0);
+        mp4_box(b"mvhd", &body)
+    }
+
+    fn tkhd(width: u32, height: u32) -> Vec<u8> {
+        let mut body = vec![0; 76];
+        body.extend_from_slice(&(width << 16).to_be_bytes());
+        body.extend_from_slice(&(height << 16).to_be_bytes());
+        mp4_box(b"tkhd", &body)
+    }
+
+    fn mp4(timescale: u32, duration: u32, tracks: &[(u32, u32)]) -> Vec<u8> {
+        let mut moov = mvhd(timescale, duration);
+        for (width, height) in tracks {
+            moov.extend(mp4_box(b"trak", &tkhd(*width, *height)));
+        }
+
+        let mut data = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        data.extend(mp4_box(b"mdat", &[0; 32]));
+        data.extend(mp4_box(b"moov", &moov));
+        data
+    }
+
+    fn ogg_page(granule: u64, packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0\0".to_vec();
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 13]);
+        page.extend_from_slice(packet);
+        page
+    }
+
+    #[test]
+    fn test_mp4_video() {
+        let data = mp4(1000, 12_400, &[(0, 0), (1280, 720)]);
+        assert_eq!(
+            detect_metadata(&MediaType::Video, &data),
+            DetectedMetadata {
+                duration_secs: Some(12),
+                width: Some(1280),
+                height: Some(720),
+            }
+        );
+    }
+
+    #[test]
+    fn test_mp4_without_moov() {
+        let data = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        assert_eq!(
+            detect_metadata(&MediaType::VideoNote, &data),
+            DetectedMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_truncated_mp4() {
+        let data = mp4(1000, 12_400, &[(1280, 720)]);
+        assert_eq!(
+            detect_metadata(&MediaType::Video, &data[..data.len() - 10]),
+            DetectedMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_png_picture() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        assert_eq!(
+            detect_metadata(&MediaType::Picture, &data),
+            DetectedMetadata {
+                duration_secs: None,
+                width: Some(640),
+                height: Some(480),
+            }
+        );
+    }
+
+    #[test]
+    fn test_ogg_opus_voice() {
+        let mut head = b"OpusHead\x01\x01".to_vec();
+        head.extend_from_slice(&312u16.to_le_bytes());
+        let mut data = ogg_page(0, &head);
+        data.extend(ogg_page(48000 * 3 + 312, &[0; 16]));
+
+        assert_eq!(
+            detect_metadata(&MediaType::Voice, &data).duration_secs,
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_data() {
+        for type_ in [MediaType::Picture, MediaType::Video, MediaType::Voice] {
+            assert_eq!(
+                detect_metadata(&type_, b"definitely not a media file"),
+                DetectedMetadata::default()
+            );
+        }
+    }
+}
@@ -0,0 +1,378 @@
+mod metadata;
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use chrono_tz::Tz;
+use cron::Schedule;
+use tracing_attributes::instrument;
+
+use crate::database::payload::MediaPayload;
+use crate::database::repository::AsyncRepository;
+use crate::database::types::{
+    MediaLocation, MediaMetadata, MediaStorage, MediaType, NewCronJob, NewMedia, NewMediaGroupItem,
+};
+use crate::formatting::validate;
+use crate::storage::{move_single_media, MediaStores};
+
+use self::metadata::detect_metadata;
+
+const MAX_THUMBNAIL_BYTES: usize = 200 * 1024;
+const MAX_THUMBNAIL_SIDE: usize = 320;
+
+/// Fills in the metadata which is not given explicitly, validates and inserts the media
+/// with its album items, then moves its blob to the requested storage.
+/// Returns the id of the inserted media.
+#[instrument(level = "trace", skip(repository, stores, media, items), fields(name = media.name))]
+pub async fn ingest_media(
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    mut media: NewMedia,
+    items: &[NewMediaGroupItem],
+    storage: MediaStorage,
+) -> anyhow::Result<i32> {
+    fill_detected_metadata(&mut media);
+    validate_media(&media)
+        .and_then(|_| validate_album_items(&media, items))
+        .map_err(|e| anyhow!("Media '{}' is rejected: {e}", media.name))?;
+
+    let id = match media.type_ {
+        MediaType::Album => repository.insert_album(&media, items).await?,
+        _ => repository.insert_media(&media).await?,
+    };
+    if media.type_.has_data() {
+        let location = MediaLocation {
+            id,
+            name: media.name,
+            storage: MediaStorage::Postgres,
+            storage_key: None,
+        };
+        move_single_media(repository, stores, &location, storage).await?;
+    }
+
+    Ok(id)
+}
+
+/// Validates and inserts the cron job which sends one of the named media.
+/// Returns the id of the inserted job.
+#[instrument(level = "trace", skip(repository, job))]
+pub async fn ingest_cron_job(
+    repository: &mut AsyncRepository,
+    job: NewCronJob,
+    media_names: &[String],
+) -> anyhow::Result<i32> {
+    validate_cron_job(&job, media_names)
+        .map_err(|e| anyhow!("Cron job '{job:?}' is rejected: {e}"))?;
+    repository.insert_cron_job(&job, media_names).await
+}
+
+pub fn validate_cron_job(job: &NewCronJob, media_names: &[String]) -> anyhow::Result<()> {
+    match (&job.pattern, job.run_at) {
+        (Some(pattern), None) => {
+            Schedule::from_str(pattern).map_err(|e| anyhow!("pattern is malformed: {e}"))?;
+        }
+        (None, Some(_)) => {}
+        _ => return Err(anyhow!("either a pattern or a time to run at is expected")),
+    }
+
+    if let Some(time_zone) = &job.time_zone {
+        Tz::from_str(time_zone).map_err(|e| anyhow!("time zone is unknown: {e}"))?;
+    }
+    if let Some(caption) = &job.caption {
+        validate(caption, &job.parse_mode).map_err(|e| anyhow!("caption is malformed: {e}"))?;
+    }
+    if media_names.is_empty() {
+        return Err(anyhow!("there is no media to send"));
+    }
+
+    Ok(())
+}
+
+fn fill_detected_metadata(media: &mut NewMedia) {
+    let Some(data) = &media.data else {
+        return;
+    };
+
+    let detected = detect_metadata(&media.type_, data);
+    let metadata = &mut media.metadata;
+    metadata.duration_secs = metadata.duration_secs.or(detected.duration_secs);
+    metadata.width = metadata.width.or(detected.width);
+    metadata.height = metadata.height.or(detected.height);
+}
+
+pub fn validate_media(media: &NewMedia) -> anyhow::Result<()> {
+    match (&media.type_, &media.data) {
+        (MediaType::Unknown, _) => return Err(anyhow!("media type is unknown")),
+        (t, Some(_)) if !t.has_data() => return Err(anyhow!("{t:?} can't have data")),
+        (t, None) if !t.has_data() => {}
+        (_, None) => return Err(anyhow!("media has no data")),
+        (MediaType::PlainText, Some(data)) => {
+            let text = std::str::from_utf8(data).map_err(|e| anyhow!("text is not UTF-8: {e}"))?;
+            validate(text, &media.parse_mode).map_err(|e| anyhow!("text is malformed: {e}"))?;
+        }
+        (_, Some(_)) => {}
+    }
+
+    MediaPayload::parse(&media.type_, media.payload.as_ref())?;
+    validate_metadata(&media.type_, &media.metadata)?;
+
+    if let Some(caption) = &media.caption {
+        validate(caption, &media.parse_mode).map_err(|e| anyhow!("caption is malformed: {e}"))?;
+    }
+
+    Ok(())
+}
+
+// Item captions are sent with the parse mode of the album.
+// Types of the items are checked when the album is sent.
+pub fn validate_album_items(media: &NewMedia, items: &[NewMediaGroupItem]) -> anyhow::Result<()> {
+    if media.type_ != MediaType::Album {
+        return match items {
+            [] => Ok(()),
+            _ => Err(anyhow!("{:?} can't have album items", media.type_)),
+        };
+    }
+
+    if !(2..=10).contains(&items.len()) {
+        return Err(anyhow!(
+            "album must have from 2 to 10 items, got {}",
+            items.len()
+        ));
+    }
+    for item in items {
+        if let Some(caption) = &item.caption {
+            validate(caption, &media.parse_mode)
+                .map_err(|e| anyhow!("caption of item '{}' is malformed: {e}", item.name))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_metadata(type_: &MediaType, metadata: &MediaMetadata) -> anyhow::Result<()> {
+    if metadata.duration_secs.is_some_and(|x| x < 0) {
+        return Err(anyhow!("duration can't be negative"));
+    }
+    if metadata.width.is_some_and(|x| x <= 0) || metadata.height.is_some_and(|x| x <= 0) {
+        return Err(anyhow!("dimensions must be positive"));
+    }
+
+    if metadata.has_spoiler
+        && !matches!(
+            type_,
+            MediaType::Picture | MediaType::Video | MediaType::Animation
+        )
+    {
+        return Err(anyhow!("{type_:?} can't be spoilered"));
+    }
+
+    // the bot api accepts the flag for voices, but SendVoice of teloxide-core 0.9.1 lacks it
+    if metadata.protect_content && matches!(type_, MediaType::Voice) {
+        return Err(anyhow!(
+            "{type_:?} can't be protected from forwarding until teloxide-core 0.9.1 is upgraded"
+        ));
+    }
+
+    if let Some(thumbnail) = &metadata.thumbnail {
+        if !matches!(
+            type_,
+            MediaType::Video
+                | MediaType::Animation
+                | MediaType::VideoNote
+                | MediaType::Audio
+                | MediaType::Document
+        ) {
+            return Err(anyhow!("{type_:?} can't have thumbnail"));
+        }
+        validate_thumbnail(thumbnail)?;
+    }
+
+    Ok(())
+}
+
+fn validate_thumbnail(thumbnail: &[u8]) -> anyhow::Result<()> {
+    if thumbnail.len() > MAX_THUMBNAIL_BYTES {
+        return Err(anyhow!(
+            "thumbnail must be up to {MAX_THUMBNAIL_BYTES} bytes, got {}",
+            thumbnail.len()
+        ));
+    }
+    if !matches!(
+        imagesize::image_type(thumbnail),
+        Ok(imagesize::ImageType::Jpeg)
+    ) {
+        return Err(anyhow!("thumbnail must be JPEG"));
+    }
+
+    let size =
+        imagesize::blob_size(thumbnail).map_err(|e| anyhow!("thumbnail is malformed: {e}"))?;
+    if size.width > MAX_THUMBNAIL_SIDE || size.height > MAX_THUMBNAIL_SIDE {
+        return Err(anyhow!(
+            "thumbnail must be up to {MAX_THUMBNAIL_SIDE}x{MAX_THUMBNAIL_SIDE}, got {}x{}",
+            size.width,
+            size.height
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::database::types::{
+        MediaType, NewCronJob, NewMedia, NewMediaGroupItem, TextParseMode,
+    };
+
+    use super::{fill_detected_metadata, validate_album_items, validate_cron_job, validate_media};
+
+    fn media(type_: MediaType, data: Option<&str>, caption: Option<&str>) -> NewMedia {
+        NewMedia {
+            name: "name".to_string(),
+            type_,
+            data: data.map(|x| x.as_bytes().to_vec()),
+            caption: caption.map(str::to_string),
+            parse_mode: TextParseMode::MarkdownV2,
+            payload: None,
+            metadata: Default::default(),
+        }
+    }
+
+    // Minimal JPEG header with the start-of-frame segment only.
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 0x08];
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x03; 10]);
+        data
+    }
+
+    #[test]
+    fn test_valid_media() {
+        assert!(validate_media(&media(MediaType::Voice, Some("data"), Some("*ok*"))).is_ok());
+        assert!(validate_media(&media(MediaType::PlainText, Some("_ok_"), None)).is_ok());
+        assert!(validate_media(&media(MediaType::Album, None, Some("ok"))).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_markup_is_rejected() {
+        assert!(validate_media(&media(MediaType::Voice, Some("data"), Some("*nope"))).is_err());
+        assert!(validate_media(&media(MediaType::PlainText, Some("nope."), None)).is_err());
+    }
+
+    #[test]
+    fn test_malformed_media_is_rejected() {
+        assert!(validate_media(&media(MediaType::Unknown, Some("data"), None)).is_err());
+        assert!(validate_media(&media(MediaType::Voice, None, None)).is_err());
+        assert!(validate_media(&media(MediaType::Album, Some("data"), None)).is_err());
+        assert!(validate_media(&media(MediaType::Poll, None, None)).is_err());
+    }
+
+    #[test]
+    fn test_payload_media() {
+        let mut poll = media(MediaType::Poll, None, None);
+        poll.payload = Some(json!({"question": "Beer?", "options": ["Yes", "No"]}));
+        assert!(validate_media(&poll).is_ok());
+
+        poll.data = Some(b"data".to_vec());
+        assert!(validate_media(&poll).is_err());
+
+        let mut audio = media(MediaType::Audio, Some("data"), None);
+        assert!(validate_media(&audio).is_ok());
+
+        audio.payload = Some(json!({"title": 42}));
+        assert!(validate_media(&audio).is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut video = media(MediaType::Video, Some("data"), None);
+        video.metadata.has_spoiler = true;
+        video.metadata.thumbnail = Some(jpeg(320, 180));
+        assert!(validate_media(&video).is_ok());
+
+        video.metadata.thumbnail = Some(jpeg(640, 360));
+        assert!(validate_media(&video).is_err());
+
+        video.metadata.thumbnail = Some(b"\x89PNG\r\n\x1a\n".to_vec());
+        assert!(validate_media(&video).is_err());
+
+        let mut voice = media(MediaType::Voice, Some("data"), None);
+        voice.metadata.has_spoiler = true;
+        assert!(validate_media(&voice).is_err());
+
+        voice.metadata.has_spoiler = false;
+        voice.metadata.protect_content = true;
+        assert!(validate_media(&voice).is_err());
+
+        let mut picture = media(MediaType::Picture, Some("data"), None);
+        picture.metadata.width = Some(0);
+        assert!(validate_media(&picture).is_err());
+    }
+
+    #[test]
+    fn test_album_items() {
+        let item = |caption: Option<&str>| NewMediaGroupItem {
+            name: "cat".to_string(),
+            caption: caption.map(str::to_string),
+        };
+        let album = media(MediaType::Album, None, None);
+        assert!(validate_album_items(&album, &[item(Some("*meow*")), item(None)]).is_ok());
+        assert!(validate_album_items(&album, &[item(Some("*meow")), item(None)]).is_err());
+        assert!(validate_album_items(&album, &[item(None)]).is_err());
+
+        let voice = media(MediaType::Voice, Some("data"), None);
+        assert!(validate_album_items(&voice, &[]).is_ok());
+        assert!(validate_album_items(&voice, &[item(None), item(None)]).is_err());
+    }
+
+    #[test]
+    fn test_explicit_metadata_wins_over_detected() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+
+        let mut picture = media(MediaType::Picture, None, None);
+        picture.data = Some(data);
+        picture.metadata.width = Some(100);
+        fill_detected_metadata(&mut picture);
+
+        assert_eq!(picture.metadata.width, Some(100));
+        assert_eq!(picture.metadata.height, Some(480));
+    }
+
+    #[test]
+    fn test_cron_job() {
+        let media = ["cat".to_string()];
+        let mut job = NewCronJob {
+            pattern: Some("0 0 9 * * *".to_string()),
+            run_at: None,
+            chat_id: Some(-100),
+            message_thread_id: None,
+            caption: Some("*morning*".to_string()),
+            description: None,
+            parse_mode: TextParseMode::MarkdownV2,
+            time_zone: Some("Europe/Kyiv".to_string()),
+        };
+        assert!(validate_cron_job(&job, &media).is_ok());
+        assert!(validate_cron_job(&job, &[]).is_err());
+
+        job.caption = Some("*morning".to_string());
+        assert!(validate_cron_job(&job, &media).is_err());
+
+        job.caption = None;
+        job.time_zone = Some("Mars/Olympus_Mons".to_string());
+        assert!(validate_cron_job(&job, &media).is_err());
+
+        job.time_zone = None;
+        job.pattern = Some("every morning".to_string());
+        assert!(validate_cron_job(&job, &media).is_err());
+
+        job.run_at = Some(chrono::Utc::now());
+        assert!(validate_cron_job(&job, &media).is_err());
+
+        job.pattern = None;
+        assert!(validate_cron_job(&job, &media).is_ok());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod bot;
 pub mod database;
+pub mod formatting;
 pub mod hyper_log_filter;
+pub mod ingest;
 pub mod schema;
+pub mod storage;
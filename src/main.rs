@@ -16,10 +16,20 @@ use tracing_unwrap::ResultExt;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server};
 
-use krusty::bot::start_bot;
-
-async fn dummy(_: Request<Body>) -> Result<Response<Body>, Infallible> {
-    Ok(Response::new(Body::from("")))
+use krusty::bot::{
+    configure_media_data_cache, media_data_cache_stats, start_bot, DuplicateConfig,
+    DEFAULT_CAPTION_TEMPLATE,
+};
+use krusty::database::repository::AsyncRepository;
+use krusty::storage::MediaStores;
+
+async fn dummy(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    match req.uri().path() {
+        "/media_cache" => Ok(Response::new(Body::from(
+            media_data_cache_stats().await.to_string(),
+        ))),
+        _ => Ok(Response::new(Body::from(""))),
+    }
 }
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
@@ -74,17 +84,47 @@ async fn main() {
     let similarity_threshold_in_decimal =
         env::var("MAX_ACCEPTED_SCORE_SIMILARITY").map_or_else(|_| 0.26f64, |x| x.parse().unwrap());
 
+    let duplicates = DuplicateConfig {
+        max_photo_hash_distance: env::var("MAX_PHOTO_HASH_DISTANCE")
+            .map_or_else(|_| 5, |x| x.parse().unwrap()),
+        max_text_hash_distance: env::var("MAX_TEXT_HASH_DISTANCE")
+            .map_or_else(|_| 10, |x| x.parse().unwrap()),
+        min_text_length: env::var("MIN_DUPLICATE_TEXT_LENGTH")
+            .map_or_else(|_| 200, |x| x.parse().unwrap()),
+        default_window_days: env::var("DUPLICATE_WINDOW_DAYS")
+            .ok()
+            .map(|x| x.parse().unwrap()),
+        caption_template: env::var("DUPLICATE_CAPTION_TEMPLATE")
+            .unwrap_or_else(|_| DEFAULT_CAPTION_TEMPLATE.to_string()),
+    };
+
+    let scheduled_message_grace_period_sec =
+        env::var("SCHEDULED_MESSAGE_GRACE_PERIOD_SEC").map_or_else(|_| 600, |x| x.parse().unwrap());
+
+    let media_cache_max_bytes =
+        env::var("MEDIA_CACHE_MAX_BYTES").map_or_else(|_| 64 * 1024 * 1024, |x| x.parse().unwrap());
+    let media_cache_max_item_bytes: Option<usize> = env::var("MEDIA_CACHE_MAX_ITEM_BYTES")
+        .ok()
+        .map(|x| x.parse().unwrap());
+    configure_media_data_cache(media_cache_max_bytes, media_cache_max_item_bytes).await;
+
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set");
 
     run_migrations(&db_url);
 
-    let mng = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url);
+    let mng = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new(db_url.clone());
     let pool = Pool::builder(mng).build().unwrap_or_log();
+    let media_stores = MediaStores::from_env(AsyncRepository::new(pool.clone())).unwrap_or_log();
 
     // should be removed once the normal non-http workers will be allowed on fly.io
     let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
     let make_service = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(dummy)) });
     let dummy_server = Server::bind(&addr).serve(make_service);
+    tokio::spawn(async move {
+        if let Err(e) = dummy_server.await {
+            log::error!("Http server failed: '{e}'");
+        }
+    });
 
     start_bot(
         bot,
@@ -92,9 +132,11 @@ async fn main() {
         Duration::seconds(ignore_message_older_than_sec),
         Percentage::from(media_being_sent_chance_in_percent),
         Percentage::from_decimal(similarity_threshold_in_decimal),
+        duplicates,
+        Duration::seconds(scheduled_message_grace_period_sec),
+        db_url,
         pool,
+        media_stores,
     )
     .await;
-
-    std::mem::drop(dummy_server);
 }
@@ -1,36 +1,111 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "duplicate_policy"))]
+    pub struct DuplicatePolicy;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "media_feature_type"))]
     pub struct MediaFeatureType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "media_storage"))]
+    pub struct MediaStorage;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "media_type"))]
     pub struct MediaType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "script_step_kind"))]
+    pub struct ScriptStepKind;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "tag_type"))]
     pub struct TagType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "text_parse_mode"))]
+    pub struct TextParseMode;
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::DuplicatePolicy;
+
     chats (id) {
         id -> Int4,
         chat_id -> Int8,
+        reply_ttl_secs -> Nullable<Int4>,
+        text_trigger_enabled -> Nullable<Bool>,
+        duplicate_detection_enabled -> Nullable<Bool>,
+        cooldown_secs -> Nullable<Int4>,
+        text_duplicate_detection_enabled -> Nullable<Bool>,
+        duplicate_window_days -> Nullable<Int4>,
+        duplicate_policy -> DuplicatePolicy,
+        mute_after_duplicates -> Nullable<Int4>,
+        mute_window_hours -> Nullable<Int4>,
+        mute_minutes -> Nullable<Int4>,
+        #[max_length = 255]
+        title -> Nullable<Varchar>,
+        active -> Bool,
+        broadcast_enabled -> Bool,
+        #[max_length = 64]
+        time_zone -> Nullable<Varchar>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::TextParseMode;
+
     cron_jobs (id) {
         id -> Int4,
         #[max_length = 255]
-        pattern -> Varchar,
+        pattern -> Nullable<Varchar>,
         chat_id -> Nullable<Int8>,
         #[max_length = 255]
         caption -> Nullable<Varchar>,
         #[max_length = 255]
         description -> Nullable<Varchar>,
+        parse_mode -> TextParseMode,
+        message_thread_id -> Nullable<Int4>,
+        #[max_length = 64]
+        time_zone -> Nullable<Varchar>,
+        run_at -> Nullable<Timestamptz>,
+        sent_at -> Nullable<Timestamptz>,
+        skipped_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::MediaFeatureType;
+
+    feature_reply_options (id) {
+        id -> Int4,
+        feature_type -> MediaFeatureType,
+        reply -> Nullable<Bool>,
+        notify -> Nullable<Bool>,
+    }
+}
+
+diesel::table! {
+    forward_fingerprints (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        #[max_length = 255]
+        origin -> Varchar,
+        forward_date -> Timestamptz,
+        content_hash -> Int8,
+        #[max_length = 255]
+        message_url -> Varchar,
+        created_at -> Timestamptz,
+        poster_id -> Nullable<Int8>,
+        #[max_length = 255]
+        poster_name -> Nullable<Varchar>,
+        repost_count -> Int4,
     }
 }
 
@@ -42,12 +117,19 @@ diesel::table! {
         #[max_length = 255]
         message_url -> Varchar,
         forwarded_chat_id -> Int8,
+        created_at -> Timestamptz,
+        poster_id -> Nullable<Int8>,
+        #[max_length = 255]
+        poster_name -> Nullable<Varchar>,
+        repost_count -> Int4,
     }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::MediaType;
+    use super::sql_types::MediaStorage;
+    use super::sql_types::TextParseMode;
 
     media (id) {
         id -> Int4,
@@ -55,7 +137,51 @@ diesel::table! {
         name -> Varchar,
         #[sql_name = "type"]
         type_ -> MediaType,
-        data -> Bytea,
+        data -> Nullable<Bytea>,
+        #[max_length = 255]
+        telegram_file_id -> Nullable<Varchar>,
+        storage -> MediaStorage,
+        #[max_length = 1024]
+        storage_key -> Nullable<Varchar>,
+        #[max_length = 1024]
+        caption -> Nullable<Varchar>,
+        parse_mode -> TextParseMode,
+        payload -> Nullable<Jsonb>,
+        reply_ttl_secs -> Nullable<Int4>,
+        duration_secs -> Nullable<Int4>,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        thumbnail -> Nullable<Bytea>,
+        has_spoiler -> Bool,
+        protect_content -> Bool,
+    }
+}
+
+diesel::table! {
+    media_group_items (id) {
+        id -> Int4,
+        group_media_id -> Int4,
+        media_id -> Int4,
+        position -> Int4,
+        #[max_length = 1024]
+        caption -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ScriptStepKind;
+
+    media_script_steps (id) {
+        id -> Int4,
+        script_media_id -> Int4,
+        position -> Int4,
+        kind -> ScriptStepKind,
+        #[max_length = 32]
+        chat_action -> Nullable<Varchar>,
+        min_delay_ms -> Nullable<Int4>,
+        max_delay_ms -> Nullable<Int4>,
+        media_id -> Nullable<Int4>,
     }
 }
 
@@ -78,6 +204,62 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    pending_deletions (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        message_id -> Int4,
+        delete_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    photo_hashes (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        hash -> Int8,
+        #[max_length = 255]
+        message_url -> Varchar,
+        created_at -> Timestamptz,
+        poster_id -> Nullable<Int8>,
+        #[max_length = 255]
+        poster_name -> Nullable<Varchar>,
+        repost_count -> Int4,
+    }
+}
+
+diesel::table! {
+    posted_links (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        #[max_length = 2048]
+        url -> Varchar,
+        #[max_length = 255]
+        message_url -> Varchar,
+        created_at -> Timestamptz,
+        poster_id -> Nullable<Int8>,
+        #[max_length = 255]
+        poster_name -> Nullable<Varchar>,
+        repost_count -> Int4,
+    }
+}
+
+diesel::table! {
+    repost_events (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        reposter_id -> Nullable<Int8>,
+        #[max_length = 255]
+        reposter_name -> Nullable<Varchar>,
+        #[max_length = 255]
+        message_url -> Varchar,
+        #[max_length = 255]
+        original_url -> Varchar,
+        original_poster_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     tag_to_media (tag_id, media_id) {
         tag_id -> Int4,
@@ -96,6 +278,36 @@ diesel::table! {
         #[sql_name = "type"]
         type_ -> TagType,
         for_whole_text -> Bool,
+        reply_ttl_secs -> Nullable<Int4>,
+        reply -> Nullable<Bool>,
+        notify -> Nullable<Bool>,
+    }
+}
+
+diesel::table! {
+    text_fingerprints (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        fingerprint -> Int8,
+        #[max_length = 255]
+        message_url -> Varchar,
+        created_at -> Timestamptz,
+        poster_id -> Nullable<Int8>,
+        #[max_length = 255]
+        poster_name -> Nullable<Varchar>,
+        repost_count -> Int4,
+    }
+}
+
+diesel::table! {
+    topic_settings (id) {
+        id -> Int4,
+        chat_id -> Int8,
+        message_thread_id -> Int4,
+        text_trigger_enabled -> Nullable<Bool>,
+        duplicate_detection_enabled -> Nullable<Bool>,
+        cooldown_secs -> Nullable<Int4>,
+        text_duplicate_detection_enabled -> Nullable<Bool>,
     }
 }
 
@@ -108,10 +320,20 @@ diesel::joinable!(tag_to_media -> tags (tag_id));
 diesel::allow_tables_to_appear_in_same_query!(
     chats,
     cron_jobs,
+    feature_reply_options,
+    forward_fingerprints,
     forwarded_messages,
     media,
+    media_group_items,
+    media_script_steps,
     media_to_cron_job,
     media_to_feature,
+    pending_deletions,
+    photo_hashes,
+    posted_links,
+    repost_events,
     tag_to_media,
     tags,
+    text_fingerprints,
+    topic_settings,
 );
@@ -0,0 +1,88 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::path::{Component, Path, PathBuf};
+
+use super::MediaStore;
+
+/// Keeps blobs as files in a local directory, the key is a path relative to the root.
+pub struct FilesystemMediaStore {
+    root: PathBuf,
+}
+
+impl FilesystemMediaStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FilesystemMediaStore { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|x| matches!(x, Component::Normal(_)))
+        {
+            return Err(anyhow!("Media key '{key}' escapes the storage directory"));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl MediaStore for FilesystemMediaStore {
+    async fn load(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(tokio::fs::read(self.path(key)?).await?)
+    }
+
+    async fn save(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        Ok(tokio::fs::write(path, data).await?)
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::MediaStore;
+
+    use super::FilesystemMediaStore;
+
+    fn store(test_name: &str) -> FilesystemMediaStore {
+        FilesystemMediaStore::new(
+            std::env::temp_dir().join(format!("krusty-{}-{test_name}", std::process::id())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_save_load_remove() {
+        let store = store("save_load_remove");
+
+        store.save("nested/1-voice.ogg", b"data").await.unwrap();
+        assert_eq!(store.load("nested/1-voice.ogg").await.unwrap(), b"data");
+
+        store.remove("nested/1-voice.ogg").await.unwrap();
+        assert!(store.load("nested/1-voice.ogg").await.is_err());
+
+        // removing a missing blob is not an error
+        store.remove("nested/1-voice.ogg").await.unwrap();
+
+        std::fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keys_outside_root_are_rejected() {
+        let store = store("outside_root");
+
+        assert!(store.save("../escape", b"data").await.is_err());
+        assert!(store.load("/etc/passwd").await.is_err());
+    }
+}
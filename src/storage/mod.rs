@@ -0,0 +1,233 @@
+mod filesystem;
+mod postgres;
+mod s3;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use std::env;
+use std::sync::Arc;
+use tracing_attributes::instrument;
+
+use crate::database::repository::AsyncRepository;
+use crate::database::types::{MediaLocation, MediaStorage};
+
+pub use self::filesystem::FilesystemMediaStore;
+pub use self::postgres::PostgresMediaStore;
+pub use self::s3::S3MediaStore;
+
+#[async_trait]
+pub trait MediaStore: Send + Sync {
+    async fn load(&self, key: &str) -> anyhow::Result<Vec<u8>>;
+    async fn save(&self, key: &str, data: &[u8]) -> anyhow::Result<()>;
+    async fn remove(&self, key: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Clone)]
+pub struct MediaStores {
+    postgres: Arc<PostgresMediaStore>,
+    filesystem: Option<Arc<FilesystemMediaStore>>,
+    s3: Option<Arc<S3MediaStore>>,
+}
+
+impl MediaStores {
+    pub fn new(repository: AsyncRepository) -> Self {
+        MediaStores {
+            postgres: Arc::new(PostgresMediaStore::new(repository)),
+            filesystem: None,
+            s3: None,
+        }
+    }
+
+    pub fn from_env(repository: AsyncRepository) -> anyhow::Result<Self> {
+        let mut stores = Self::new(repository);
+
+        if let Ok(root) = env::var("MEDIA_FS_ROOT") {
+            stores = stores.with_filesystem(FilesystemMediaStore::new(root));
+        }
+
+        if let Ok(bucket) = env::var("MEDIA_S3_BUCKET") {
+            let endpoint = env::var("MEDIA_S3_ENDPOINT")
+                .map_err(|_| anyhow!("MEDIA_S3_ENDPOINT is not set"))?;
+            let region = env::var("MEDIA_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+            let access_key = env::var("MEDIA_S3_ACCESS_KEY")
+                .map_err(|_| anyhow!("MEDIA_S3_ACCESS_KEY is not set"))?;
+            let secret_key = env::var("MEDIA_S3_SECRET_KEY")
+                .map_err(|_| anyhow!("MEDIA_S3_SECRET_KEY is not set"))?;
+
+            stores = stores.with_s3(S3MediaStore::new(
+                &bucket,
+                region,
+                endpoint,
+                &access_key,
+                &secret_key,
+            )?);
+        }
+
+        Ok(stores)
+    }
+
+    pub fn with_filesystem(mut self, store: FilesystemMediaStore) -> Self {
+        self.filesystem = Some(Arc::new(store));
+        self
+    }
+
+    pub fn with_s3(mut self, store: S3MediaStore) -> Self {
+        self.s3 = Some(Arc::new(store));
+        self
+    }
+
+    pub fn store(&self, storage: MediaStorage) -> anyhow::Result<&dyn MediaStore> {
+        match storage {
+            MediaStorage::Postgres => Ok(self.postgres.as_ref()),
+            MediaStorage::Filesystem => self
+                .filesystem
+                .as_deref()
+                .map(|x| x as &dyn MediaStore)
+                .ok_or_else(|| anyhow!("Filesystem media storage is not configured")),
+            MediaStorage::S3 => self
+                .s3
+                .as_deref()
+                .map(|x| x as &dyn MediaStore)
+                .ok_or_else(|| anyhow!("S3 media storage is not configured")),
+        }
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    pub async fn load(&self, location: &MediaLocation) -> anyhow::Result<Vec<u8>> {
+        self.store(location.storage)?
+            .load(&storage_key(location, location.storage))
+            .await
+    }
+}
+
+/// Moves every blob kept in `from` storage to `to` storage, returns the number of moved media.
+/// The media row is repointed before the source blob is removed, so an interrupted run
+/// leaves at most an orphaned copy behind.
+pub async fn move_media(
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    from: MediaStorage,
+    to: MediaStorage,
+) -> anyhow::Result<usize> {
+    if from == to {
+        return Ok(0);
+    }
+
+    let source = stores.store(from)?;
+    let target = stores.store(to)?;
+
+    let locations = repository.media_locations_by_storage(from).await?;
+    for location in &locations {
+        move_location(repository, source, target, location, to).await?;
+        log::info!("Moved media '{}' from {from:?} to {to:?}", location.name);
+    }
+
+    Ok(locations.len())
+}
+
+/// Moves a blob of a single media to `to` storage.
+pub async fn move_single_media(
+    repository: &mut AsyncRepository,
+    stores: &MediaStores,
+    location: &MediaLocation,
+    to: MediaStorage,
+) -> anyhow::Result<()> {
+    if location.storage == to {
+        return Ok(());
+    }
+
+    let source = stores.store(location.storage)?;
+    let target = stores.store(to)?;
+    move_location(repository, source, target, location, to).await
+}
+
+async fn move_location(
+    repository: &mut AsyncRepository,
+    source: &dyn MediaStore,
+    target: &dyn MediaStore,
+    location: &MediaLocation,
+    to: MediaStorage,
+) -> anyhow::Result<()> {
+    let source_key = storage_key(location, location.storage);
+    let target_key = storage_key(location, to);
+
+    let data = source.load(&source_key).await?;
+    target.save(&target_key, &data).await?;
+
+    let key = (to != MediaStorage::Postgres).then_some(target_key.as_str());
+    repository
+        .update_media_location(location.id, to, key)
+        .await?;
+
+    source.remove(&source_key).await
+}
+
+/// Blobs in Postgres are addressed by media name, other storages use the key stored in
+/// the media row or, for media which is not there yet, a key derived from its id and name.
+fn storage_key(location: &MediaLocation, storage: MediaStorage) -> String {
+    if storage == MediaStorage::Postgres {
+        return location.name.clone();
+    }
+
+    match (&location.storage_key, location.storage == storage) {
+        (Some(key), true) => key.clone(),
+        _ => format!(
+            "{}-{}",
+            location.id,
+            location
+                .name
+                .chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+                    _ => '_',
+                })
+                .collect::<String>()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::types::{MediaLocation, MediaStorage};
+
+    use super::storage_key;
+
+    fn location(storage: MediaStorage, storage_key: Option<&str>) -> MediaLocation {
+        MediaLocation {
+            id: 42,
+            name: "../funny cat.mp4".to_string(),
+            storage,
+            storage_key: storage_key.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_postgres_key_is_name() {
+        let l = location(MediaStorage::S3, Some("key"));
+        assert_eq!(storage_key(&l, MediaStorage::Postgres), "../funny cat.mp4");
+    }
+
+    #[test]
+    fn test_stored_key_is_used_for_own_storage() {
+        let l = location(MediaStorage::S3, Some("key"));
+        assert_eq!(storage_key(&l, MediaStorage::S3), "key");
+    }
+
+    #[test]
+    fn test_derived_key_is_sanitized() {
+        let l = location(MediaStorage::Postgres, None);
+        assert_eq!(
+            storage_key(&l, MediaStorage::Filesystem),
+            "42-.._funny_cat.mp4"
+        );
+    }
+
+    #[test]
+    fn test_derived_key_for_other_storage() {
+        let l = location(MediaStorage::S3, Some("key"));
+        assert_eq!(
+            storage_key(&l, MediaStorage::Filesystem),
+            "42-.._funny_cat.mp4"
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::database::repository::AsyncRepository;
+
+use super::MediaStore;
+
+/// Keeps blobs in `media.data`, the key is the media name.
+pub struct PostgresMediaStore {
+    repository: AsyncRepository,
+}
+
+impl PostgresMediaStore {
+    pub fn new(repository: AsyncRepository) -> Self {
+        PostgresMediaStore { repository }
+    }
+}
+
+#[async_trait]
+impl MediaStore for PostgresMediaStore {
+    async fn load(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        self.repository
+            .clone()
+            .media_data_by_name(key)
+            .await?
+            .ok_or_else(|| anyhow!("No data in Postgres for media '{key}'"))
+    }
+
+    async fn save(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        self.repository
+            .clone()
+            .update_media_data(key, Some(data))
+            .await
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.repository.clone().update_media_data(key, None).await
+    }
+}
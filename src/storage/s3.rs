@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use s3::{creds::Credentials, Bucket, Region};
+
+use super::MediaStore;
+
+/// Keeps blobs in an S3-compatible bucket (AWS, MinIO, etc.), the key is an object path.
+pub struct S3MediaStore {
+    bucket: Bucket,
+}
+
+impl S3MediaStore {
+    pub fn new(
+        bucket: &str,
+        region: String,
+        endpoint: String,
+        access_key: &str,
+        secret_key: &str,
+    ) -> anyhow::Result<Self> {
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)?;
+        let bucket = Bucket::new(bucket, Region::Custom { region, endpoint }, credentials)?
+            .with_path_style();
+
+        Ok(S3MediaStore { bucket })
+    }
+}
+
+#[async_trait]
+impl MediaStore for S3MediaStore {
+    async fn load(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(self.bucket.get_object(key).await?.bytes().to_vec())
+    }
+
+    async fn save(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        self.bucket.put_object(key, data).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.bucket.delete_object(key).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::storage::MediaStore;
+
+    use super::S3MediaStore;
+
+    // Runs against a local MinIO, e.g.:
+    // docker run -p 9000:9000 minio/minio server /data && mc mb local/krusty-test
+    // MEDIA_S3_TEST_ENDPOINT=http://127.0.0.1:9000 cargo test -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn test_save_load_remove() {
+        let store = S3MediaStore::new(
+            &env::var("MEDIA_S3_TEST_BUCKET").unwrap_or_else(|_| "krusty-test".to_string()),
+            "us-east-1".to_string(),
+            env::var("MEDIA_S3_TEST_ENDPOINT").expect("MEDIA_S3_TEST_ENDPOINT is not set"),
+            &env::var("MEDIA_S3_TEST_ACCESS_KEY").unwrap_or_else(|_| "minioadmin".to_string()),
+            &env::var("MEDIA_S3_TEST_SECRET_KEY").unwrap_or_else(|_| "minioadmin".to_string()),
+        )
+        .unwrap();
+
+        store.save("1-voice.ogg", b"data").await.unwrap();
+        assert_eq!(store.load("1-voice.ogg").await.unwrap(), b"data");
+
+        store.remove("1-voice.ogg").await.unwrap();
+        assert!(store.load("1-voice.ogg").await.is_err());
+    }
+}